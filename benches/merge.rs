@@ -0,0 +1,137 @@
+use std::cmp::Ordering;
+use std::collections::{ BTreeSet, HashMap, HashSet };
+
+use criterion::{ criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion };
+
+use spinach::merge::{ Merge, BitSet, Intersect, MapUnion, Max, SortedVec, Union };
+
+
+const SMALL: u64 = 16;
+const LARGE: u64 = 100_000;
+
+// Previous implementations, for comparison.
+mod naive {
+    use super::*;
+
+    pub fn union<T: Ord>(val: &mut BTreeSet<T>, other: BTreeSet<T>) {
+        val.extend(other);
+    }
+
+    pub fn union_cmp<T: Ord>(val: &BTreeSet<T>, other: &BTreeSet<T>) -> Option<Ordering> {
+        let s = val.union(other).count();
+        if s != val.len() && s != other.len() {
+            None
+        }
+        else if s == val.len() {
+            if s == other.len() {
+                Some(Ordering::Equal)
+            }
+            else {
+                Some(Ordering::Greater)
+            }
+        }
+        else {
+            Some(Ordering::Less)
+        }
+    }
+
+    pub fn intersect<T: Ord>(val: &mut BTreeSet<T>, other: BTreeSet<T>) {
+        *val = other.into_iter()
+            .filter(|x| val.contains(x))
+            .collect();
+    }
+
+    pub fn map_union(val: &mut HashMap<u64, u64>, other: HashMap<u64, u64>) {
+        for (k, v) in other {
+            let val_v = val.entry(k).or_insert(v);
+            *val_v = v.max(*val_v);
+        }
+    }
+}
+
+fn range<C: std::iter::FromIterator<u64>>(n: u64) -> C {
+    (0..n).collect()
+}
+
+
+fn bench_union(c: &mut Criterion) {
+    let mut group = c.benchmark_group("union_small_into_large");
+    // `val` is small and `other` is large, the bad case for extending `val`.
+    group.bench_function("naive", |b| b.iter_batched(
+        || ( range::<BTreeSet<_>>(SMALL), range::<BTreeSet<_>>(LARGE) ),
+        |( mut val, other )| { naive::union(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("btree_set", |b| b.iter_batched(
+        || ( range::<BTreeSet<_>>(SMALL), range::<BTreeSet<_>>(LARGE) ),
+        |( mut val, other )| { Union::<BTreeSet<_>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("hash_set", |b| b.iter_batched(
+        || ( range::<HashSet<_>>(SMALL), range::<HashSet<_>>(LARGE) ),
+        |( mut val, other )| { Union::<HashSet<_>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.finish();
+
+    let mut group = c.benchmark_group("union_dense");
+    group.bench_function("btree_set", |b| b.iter_batched(
+        || ( range::<BTreeSet<_>>(LARGE), (LARGE / 2..3 * LARGE / 2).collect::<BTreeSet<_>>() ),
+        |( mut val, other )| { Union::<BTreeSet<_>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("sorted_vec", |b| b.iter_batched(
+        || ( range::<SortedVec<_>>(LARGE), (LARGE / 2..3 * LARGE / 2).collect::<SortedVec<_>>() ),
+        |( mut val, other )| { Union::<SortedVec<_>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("bit_set", |b| b.iter_batched(
+        || ( (0..LARGE as usize).collect::<BitSet>(), (LARGE as usize / 2..3 * LARGE as usize / 2).collect::<BitSet>() ),
+        |( mut val, other )| { Union::<BitSet>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.finish();
+}
+
+fn bench_partial_cmp(c: &mut Criterion) {
+    let mut group = c.benchmark_group("union_partial_cmp");
+    for &small in &[ SMALL, LARGE / 2 ] {
+        let val = range::<BTreeSet<_>>(small);
+        let other = range::<BTreeSet<_>>(LARGE);
+        group.bench_with_input(BenchmarkId::new("naive", small), &( &val, &other ),
+            |b, ( val, other )| b.iter(|| naive::union_cmp(val, other)));
+        group.bench_with_input(BenchmarkId::new("btree_set", small), &( &val, &other ),
+            |b, ( val, other )| b.iter(|| Union::<BTreeSet<_>>::partial_cmp(*val, *other)));
+    }
+    group.finish();
+}
+
+fn bench_intersect(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intersect");
+    group.bench_function("naive", |b| b.iter_batched(
+        || ( range::<BTreeSet<_>>(LARGE), range::<BTreeSet<_>>(SMALL) ),
+        |( mut val, other )| { naive::intersect(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("btree_set", |b| b.iter_batched(
+        || ( range::<BTreeSet<_>>(LARGE), range::<BTreeSet<_>>(SMALL) ),
+        |( mut val, other )| { Intersect::<BTreeSet<_>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("hash_set", |b| b.iter_batched(
+        || ( range::<HashSet<_>>(LARGE), range::<HashSet<_>>(SMALL) ),
+        |( mut val, other )| { Intersect::<HashSet<_>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.finish();
+}
+
+fn bench_map_union(c: &mut Criterion) {
+    let map = |n: u64| (0..n).map(|k| ( k, k )).collect::<HashMap<_, _>>();
+
+    let mut group = c.benchmark_group("map_union_small_into_large");
+    group.bench_function("naive", |b| b.iter_batched(
+        || ( map(SMALL), map(LARGE) ),
+        |( mut val, other )| { naive::map_union(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.bench_function("hash_map", |b| b.iter_batched(
+        || ( map(SMALL), map(LARGE) ),
+        |( mut val, other )| { MapUnion::<HashMap<_, Max<_>>>::merge_in(&mut val, other); val },
+        BatchSize::LargeInput));
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_union, bench_partial_cmp, bench_intersect, bench_map_union);
+criterion_main!(benches);
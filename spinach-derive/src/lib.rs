@@ -0,0 +1,83 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{ parse_macro_input, Data, DeriveInput, Fields, Index, Member, Type };
+use syn::spanned::Spanned;
+
+/// Derives `spinach::merge::Merge` for a struct whose fields are each a lattice.
+///
+/// Every field must be annotated with the merge used for it, e.g.
+/// `#[merge(Max<u64>)]`. The struct becomes its own `Domain`: `merge_in`
+/// merges field by field, and `partial_cmp` is the pointwise product order
+/// (`None` as soon as two fields disagree).
+#[proc_macro_derive(Merge, attributes(merge))]
+pub fn derive_merge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match impl_merge(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn impl_merge(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(syn::Error::new(input.ident.span(), "`Merge` can only be derived for structs")),
+    };
+    if let Fields::Unit = fields {
+        return Err(syn::Error::new(input.ident.span(), "`Merge` cannot be derived for unit structs"));
+    }
+
+    let mut members = Vec::new();
+    let mut merges = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        members.push(member);
+        merges.push(field_merge(field)?);
+    }
+
+    let name = &input.ident;
+    let ( impl_generics, ty_generics, where_clause ) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::spinach::merge::Merge for #name #ty_generics #where_clause {
+            type Domain = Self;
+
+            fn merge_in(val: &mut Self, other: Self) {
+                #(
+                    <#merges as ::spinach::merge::Merge>::merge_in(&mut val.#members, other.#members);
+                )*
+            }
+
+            fn partial_cmp(val: &Self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                let mut result = ::core::cmp::Ordering::Equal;
+                #(
+                    let cmp = <#merges as ::spinach::merge::Merge>::partial_cmp(&val.#members, &other.#members)?;
+                    if cmp != ::core::cmp::Ordering::Equal {
+                        if result == ::core::cmp::Ordering::Equal {
+                            result = cmp;
+                        }
+                        else if result != cmp {
+                            return ::core::option::Option::None;
+                        }
+                    }
+                )*
+                ::core::option::Option::Some(result)
+            }
+        }
+    })
+}
+
+/// Reads the merge type out of a field's `#[merge(...)]` attribute.
+fn field_merge(field: &syn::Field) -> syn::Result<Type> {
+    let mut attrs = field.attrs.iter().filter(|attr| attr.path.is_ident("merge"));
+    let attr = attrs.next()
+        .ok_or_else(|| syn::Error::new(field.span(), "missing `#[merge(...)]` attribute on field"))?;
+    if let Some(extra) = attrs.next() {
+        return Err(syn::Error::new(extra.span(), "duplicate `#[merge(...)]` attribute on field"));
+    }
+    attr.parse_args()
+}
@@ -0,0 +1,58 @@
+use std::cmp::Ordering;
+use std::collections::{ HashMap, HashSet };
+
+use spinach::merge::{ Merge, MapUnion, Max, Min, Union };
+
+
+#[derive(spinach_derive::Merge, Clone, Debug, PartialEq, Eq)]
+struct Record {
+    #[merge(Max<u64>)]
+    version: u64,
+    #[merge(Min<u64>)]
+    first_seen: u64,
+    #[merge(Union<HashSet<&'static str>>)]
+    tags: HashSet<&'static str>,
+    #[merge(MapUnion<HashMap<&'static str, Max<u32>>>)]
+    counts: HashMap<&'static str, u32>,
+}
+
+#[derive(spinach_derive::Merge, Clone, Debug, PartialEq, Eq)]
+struct Bounds(#[merge(Min<i32>)] i32, #[merge(Max<i32>)] i32);
+
+
+fn record(version: u64, first_seen: u64, tags: &[&'static str], counts: &[(&'static str, u32)]) -> Record {
+    Record {
+        version: version,
+        first_seen: first_seen,
+        tags: tags.iter().cloned().collect(),
+        counts: counts.iter().cloned().collect(),
+    }
+}
+
+#[test]
+pub fn test_merge_in() {
+    let mut a = record(1, 10, &[ "x" ], &[ ( "foo", 1 ) ]);
+    let b = record(2, 20, &[ "y" ], &[ ( "foo", 3 ), ( "bar", 2 ) ]);
+    Record::merge_in(&mut a, b);
+    assert_eq!(record(2, 10, &[ "x", "y" ], &[ ( "foo", 3 ), ( "bar", 2 ) ]), a);
+
+    let mut bounds = Bounds(0, 5);
+    Bounds::merge_in(&mut bounds, Bounds(-3, 2));
+    assert_eq!(Bounds(-3, 5), bounds);
+}
+
+#[test]
+pub fn test_partial_cmp() {
+    let a = record(1, 10, &[ "x" ], &[ ( "foo", 1 ) ]);
+    let b = record(2, 10, &[ "x", "y" ], &[ ( "foo", 1 ) ]);
+    let c = record(2, 20, &[ "x" ], &[ ( "foo", 1 ) ]);
+
+    assert_eq!(Some(Ordering::Equal), Record::partial_cmp(&a, &a.clone()));
+    assert_eq!(Some(Ordering::Less), Record::partial_cmp(&a, &b));
+    assert_eq!(Some(Ordering::Greater), Record::partial_cmp(&b, &a));
+    // `version` grew but `first_seen` shrank (in `Min` order).
+    assert_eq!(None, Record::partial_cmp(&a, &c));
+
+    assert_eq!(Some(Ordering::Less), Bounds::partial_cmp(&Bounds(0, 5), &Bounds(-1, 5)));
+    assert_eq!(None, Bounds::partial_cmp(&Bounds(0, 5), &Bounds(1, 6)));
+}
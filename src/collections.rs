@@ -0,0 +1,344 @@
+use std::cmp::{ Ordering, Reverse };
+use std::hash::{ BuildHasher, Hash };
+use std::collections::{ BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet };
+use std::collections::{ btree_map, btree_set, hash_map, hash_set };
+
+// COLLECTION TRAITS //
+// Implement these for your own containers to use them in `Union`,
+// `Intersect`, `MapUnion`, and `MapIntersection`.
+
+/// Set collection, for `Union<S>` and `Intersect<S>`.
+pub trait SetLike: Default + IntoIterator + Extend<<Self as IntoIterator>::Item> {
+    type Iter<'a>: Iterator<Item = &'a Self::Item>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        0 == self.len()
+    }
+
+    fn contains(&self, item: &Self::Item) -> bool;
+
+    /// Returns if `item` was newly inserted.
+    fn insert(&mut self, item: Self::Item) -> bool;
+
+    fn retain(&mut self, f: impl FnMut(&Self::Item) -> bool);
+
+    fn iter(&self) -> Self::Iter<'_>;
+
+    fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|x| other.contains(x))
+    }
+
+    /// Inserts every item of `others`. Override this when a large batch can
+    /// be merged faster than one item at a time.
+    fn extend_all(&mut self, others: Vec<Self>) {
+        for other in others {
+            self.extend(other);
+        }
+    }
+}
+
+/// Map collection, for `MapUnion<M>` and `MapIntersection<M>`. In those
+/// merges `M` holds the value merges, e.g. `HashMap<K, Max<u64>>`, and the
+/// domain is `M::Rebind` holding the merges' domains, e.g. `HashMap<K, u64>`.
+pub trait MapLike: Default + IntoIterator<Item = (Self::Key, Self::Val)> {
+    type Key;
+    type Val;
+    type Iter<'a>: Iterator<Item = (&'a Self::Key, &'a Self::Val)>
+    where
+        Self: 'a;
+    /// The same kind of map, holding `V` values instead.
+    type Rebind<V>: MapLike<Key = Self::Key, Val = V>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        0 == self.len()
+    }
+
+    fn get(&self, key: &Self::Key) -> Option<&Self::Val>;
+
+    fn get_mut(&mut self, key: &Self::Key) -> Option<&mut Self::Val>;
+
+    fn insert(&mut self, key: Self::Key, val: Self::Val) -> Option<Self::Val>;
+
+    fn remove(&mut self, key: &Self::Key) -> Option<Self::Val>;
+
+    fn retain(&mut self, f: impl FnMut(&Self::Key, &mut Self::Val) -> bool);
+
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// Inserts `val` at `key`, or merges it into the existing value with
+    /// `merge`. Override this to avoid looking up `key` twice.
+    fn insert_or_merge(&mut self, key: Self::Key, val: Self::Val, merge: impl FnOnce(&mut Self::Val, Self::Val)) {
+        match self.get_mut(&key) {
+            Some(old) => merge(old, val),
+            None => {
+                self.insert(key, val);
+            },
+        }
+    }
+
+    /// Inserts every entry of `others`, merging values of equal keys with
+    /// `merge`. Override this when a large batch can be merged faster than
+    /// one entry at a time.
+    fn extend_all(&mut self, others: Vec<Self>, mut merge: impl FnMut(&mut Self::Val, Self::Val)) {
+        for other in others {
+            for (k, v) in other {
+                self.insert_or_merge(k, v, &mut merge);
+            }
+        }
+    }
+}
+
+
+
+// STD IMPLS //
+
+impl <T: Eq + Hash, S: BuildHasher + Default> SetLike for HashSet<T, S> {
+    type Iter<'a> = hash_set::Iter<'a, T> where Self: 'a;
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        HashSet::contains(self, item)
+    }
+
+    fn insert(&mut self, item: T) -> bool {
+        HashSet::insert(self, item)
+    }
+
+    fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        HashSet::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        HashSet::iter(self)
+    }
+
+    fn is_subset(&self, other: &Self) -> bool {
+        HashSet::is_subset(self, other)
+    }
+}
+
+impl <T: Ord> SetLike for BTreeSet<T> {
+    type Iter<'a> = btree_set::Iter<'a, T> where Self: 'a;
+
+    fn len(&self) -> usize {
+        BTreeSet::len(self)
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        BTreeSet::contains(self, item)
+    }
+
+    fn insert(&mut self, item: T) -> bool {
+        BTreeSet::insert(self, item)
+    }
+
+    fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        BTreeSet::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeSet::iter(self)
+    }
+
+    fn is_subset(&self, other: &Self) -> bool {
+        BTreeSet::is_subset(self, other)
+    }
+
+    fn extend_all(&mut self, others: Vec<Self>) {
+        let len: usize = others.iter().map(BTreeSet::len).sum();
+        if len < self.len() {
+            // Small batch, cheaper to insert into the existing tree.
+            for other in others {
+                self.extend(other);
+            }
+        }
+        else {
+            // K-way merge of the sorted runs, then build the tree bottom-up
+            // from the sorted items (`collect`'s sort is linear on sorted input).
+            let runs = std::iter::once(std::mem::take(self))
+                .chain(others)
+                .map(|set| set.into_iter().map(|x| ( x, () )))
+                .collect();
+            *self = kway_merge(runs, |_, _| ())
+                .into_iter()
+                .map(|( x, () )| x)
+                .collect();
+        }
+    }
+}
+
+impl <K: Eq + Hash, V, S: BuildHasher + Default> MapLike for HashMap<K, V, S> {
+    type Key = K;
+    type Val = V;
+    type Iter<'a> = hash_map::Iter<'a, K, V> where Self: 'a;
+    type Rebind<W> = HashMap<K, W, S>;
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        HashMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> Option<V> {
+        HashMap::insert(self, key, val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn retain(&mut self, f: impl FnMut(&K, &mut V) -> bool) {
+        HashMap::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        HashMap::iter(self)
+    }
+
+    fn insert_or_merge(&mut self, key: K, val: V, merge: impl FnOnce(&mut V, V)) {
+        match self.entry(key) {
+            hash_map::Entry::Occupied(mut kv) => {
+                merge(kv.get_mut(), val);
+            },
+            hash_map::Entry::Vacant(kv) => {
+                kv.insert(val);
+            },
+        }
+    }
+}
+
+impl <K: Ord, V> MapLike for BTreeMap<K, V> {
+    type Key = K;
+    type Val = V;
+    type Iter<'a> = btree_map::Iter<'a, K, V> where Self: 'a;
+    type Rebind<W> = BTreeMap<K, W>;
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> Option<V> {
+        BTreeMap::insert(self, key, val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn retain(&mut self, f: impl FnMut(&K, &mut V) -> bool) {
+        BTreeMap::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeMap::iter(self)
+    }
+
+    fn insert_or_merge(&mut self, key: K, val: V, merge: impl FnOnce(&mut V, V)) {
+        match self.entry(key) {
+            btree_map::Entry::Occupied(mut kv) => {
+                merge(kv.get_mut(), val);
+            },
+            btree_map::Entry::Vacant(kv) => {
+                kv.insert(val);
+            },
+        }
+    }
+
+    fn extend_all(&mut self, others: Vec<Self>, mut merge: impl FnMut(&mut V, V)) {
+        let len: usize = others.iter().map(BTreeMap::len).sum();
+        if len < self.len() {
+            // Small batch, cheaper to insert into the existing tree.
+            for other in others {
+                for (k, v) in other {
+                    self.insert_or_merge(k, v, &mut merge);
+                }
+            }
+            return;
+        }
+
+        // K-way merge of the sorted runs, then build the tree bottom-up.
+        let runs = std::iter::once(std::mem::take(self))
+            .chain(others)
+            .map(BTreeMap::into_iter)
+            .collect();
+        let merged = kway_merge(runs, merge);
+        *self = merged.into_iter().collect();
+    }
+}
+
+
+
+// K-WAY MERGE //
+
+/// The next entry of one run in `kway_merge`, ordered by key then by run so
+/// equal keys come out in run order.
+struct Head<K, V> {
+    key: K,
+    val: V,
+    run: usize,
+}
+impl <K: Ord, V> PartialEq for Head<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        Ordering::Equal == self.cmp(other)
+    }
+}
+impl <K: Ord, V> Eq for Head<K, V> {}
+impl <K: Ord, V> PartialOrd for Head<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl <K: Ord, V> Ord for Head<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key).then(self.run.cmp(&other.run))
+    }
+}
+
+/// Merges `runs`, each sorted by key without duplicates, into one sorted
+/// `Vec`, combining the values of equal keys with `merge` in run order. Keeps
+/// a min-heap of each run's next entry, so this takes `O(n log k)` time for
+/// `n` entries in `k` runs.
+fn kway_merge<K: Ord, V, I: Iterator<Item = (K, V)>>(mut runs: Vec<I>, mut merge: impl FnMut(&mut V, V)) -> Vec<(K, V)> {
+    let mut heap = BinaryHeap::with_capacity(runs.len());
+    for ( run, iter ) in runs.iter_mut().enumerate() {
+        if let Some(( key, val )) = iter.next() {
+            heap.push(Reverse(Head { key, val, run }));
+        }
+    }
+
+    let len = heap.len() + runs.iter().map(|iter| iter.size_hint().0).sum::<usize>();
+    let mut merged: Vec<(K, V)> = Vec::with_capacity(len);
+    while let Some(Reverse(Head { key, val, run })) = heap.pop() {
+        if let Some(( key, val )) = runs[run].next() {
+            heap.push(Reverse(Head { key, val, run }));
+        }
+        match merged.last_mut() {
+            Some(( last_key, last_val )) if *last_key == key => merge(last_val, val),
+            _ => merged.push(( key, val )),
+        }
+    }
+    merged
+}
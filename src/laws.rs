@@ -0,0 +1,495 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+use crate::merge::{ Merge, Dual, Meet, MergeRef };
+
+#[cfg(feature = "quickcheck")]
+use std::collections::HashMap;
+#[cfg(feature = "quickcheck")]
+use std::hash::Hash;
+
+#[cfg(feature = "quickcheck")]
+use quickcheck::{ Arbitrary, Gen };
+
+#[cfg(feature = "quickcheck")]
+use crate::merge::{ BitSet, BloomFilter, HyperLogLog, SortedVec };
+#[cfg(feature = "quickcheck")]
+use crate::merge::{ Bias, GCounter, LwwElementSet, LwwRegister, OrSet, PnCounter, TwoPhaseSet, VClock };
+
+// LATTICE LAW CHECKS //
+// Run these against any `Merge` impl, with sample values from a generator
+// such as the `quickcheck` harness below, to test that it is really a
+// semilattice.
+
+/// `merge(a, a) == a`.
+pub fn check_idempotent<F: Merge>(a: &F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    idempotent::<F>(a, &PartialEq::eq)
+}
+
+/// `merge(a, b) == merge(b, a)`.
+pub fn check_commutative<F: Merge>(a: &F::Domain, b: &F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    commutative::<F>(a, b, &PartialEq::eq)
+}
+
+/// `merge(merge(a, b), c) == merge(a, merge(b, c))`.
+pub fn check_associative<F: Merge>(a: &F::Domain, b: &F::Domain, c: &F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    associative::<F>(a, b, c, &PartialEq::eq)
+}
+
+/// `partial_cmp` agrees with `merge_in`: `a <= b` exactly when `merge(a, b) == b`.
+/// Also checks `partial_cmp` is reflexive and antisymmetric.
+pub fn check_partial_cmp<F: Merge>(a: &F::Domain, b: &F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    partial_cmp::<F>(a, b, &PartialEq::eq)
+}
+
+/// Runs every check on every combination of `samples`.
+/// This is cubic in the number of samples, so keep it small.
+pub fn check_all<F: Merge>(samples: &[F::Domain]) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    check_all_by::<F>(samples, PartialEq::eq)
+}
+
+/// `check_all`, comparing values with `eq` instead of `==`. Use this for
+/// domains whose `==` is not an equivalence, e.g. floats, where `NaN != NaN`
+/// and `-0.0 == 0.0`: compare their bits instead.
+pub fn check_all_by<F: Merge>(samples: &[F::Domain], eq: impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    for a in samples {
+        idempotent::<F>(a, &eq)?;
+        for b in samples {
+            commutative::<F>(a, b, &eq)?;
+            partial_cmp::<F>(a, b, &eq)?;
+            for c in samples {
+                associative::<F>(a, b, c, &eq)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `merge_in_ref(a, &b)` gives the same result as `merge_in(a, b)`.
+pub fn check_merge_ref<F: MergeRef>(a: &F::Domain, b: &F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    let mut ab = a.clone();
+    F::merge_in_ref(&mut ab, b);
+    let expected = merge::<F>(a, b);
+    if ab != expected {
+        return Err(format!("merge_in_ref disagrees with merge_in: merge_in_ref({:?}, {:?}) = {:?}, but merge = {:?}.",
+            a, b, ab, expected));
+    }
+    Ok(())
+}
+
+/// `merge_all(a, others)` gives the same result as merging each of `others`
+/// into `a` in turn.
+pub fn check_merge_all<F: Merge>(a: &F::Domain, others: &[F::Domain]) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    let mut all = a.clone();
+    F::merge_all(&mut all, others.iter().cloned());
+    let expected = others.iter().fold(a.clone(), |acc, other| merge::<F>(&acc, other));
+    if all != expected {
+        return Err(format!("merge_all disagrees with merge_in: merge_all({:?}, {:?}) = {:?}, but merge = {:?}.",
+            a, others, all, expected));
+    }
+    Ok(())
+}
+
+/// `merge(a, meet(a, b)) == a` and `meet(a, merge(a, b)) == a`.
+pub fn check_absorption<F: Meet>(a: &F::Domain, b: &F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    absorption::<F>(a, b, &PartialEq::eq)
+}
+
+/// Runs every check on every combination of `samples`, for both the join
+/// and the meet of a full lattice.
+pub fn check_all_lattice<F: Meet>(samples: &[F::Domain]) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    check_all_lattice_by::<F>(samples, PartialEq::eq)
+}
+
+/// `check_all_lattice`, comparing values with `eq` instead of `==`.
+pub fn check_all_lattice_by<F: Meet>(samples: &[F::Domain], eq: impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    check_all_by::<F>(samples, &eq)?;
+    check_all_by::<Dual<F>>(samples, &eq)?;
+    for a in samples {
+        for b in samples {
+            absorption::<F>(a, b, &eq)?;
+        }
+    }
+    Ok(())
+}
+
+fn idempotent<F: Merge>(a: &F::Domain, eq: &impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let aa = merge::<F>(a, a);
+    if !eq(&aa, a) {
+        return Err(format!("Not idempotent: merge({:?}, itself) = {:?}.", a, aa));
+    }
+    Ok(())
+}
+
+fn commutative<F: Merge>(a: &F::Domain, b: &F::Domain, eq: &impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let ab = merge::<F>(a, b);
+    let ba = merge::<F>(b, a);
+    if !eq(&ab, &ba) {
+        return Err(format!("Not commutative: merge({:?}, {:?}) = {:?}, but reversed = {:?}.", a, b, ab, ba));
+    }
+    Ok(())
+}
+
+fn associative<F: Merge>(a: &F::Domain, b: &F::Domain, c: &F::Domain, eq: &impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let ab_c = merge::<F>(&merge::<F>(a, b), c);
+    let a_bc = merge::<F>(a, &merge::<F>(b, c));
+    if !eq(&ab_c, &a_bc) {
+        return Err(format!("Not associative: merge(merge({:?}, {:?}), {:?}) = {:?}, but merge(a, merge(b, c)) = {:?}.",
+            a, b, c, ab_c, a_bc));
+    }
+    Ok(())
+}
+
+fn partial_cmp<F: Merge>(a: &F::Domain, b: &F::Domain, eq: &impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let aa = F::partial_cmp(a, a);
+    if Some(Ordering::Equal) != aa {
+        return Err(format!("partial_cmp not reflexive: partial_cmp({:?}, itself) = {:?}.", a, aa));
+    }
+
+    let ab = F::partial_cmp(a, b);
+    let ba = F::partial_cmp(b, a);
+    if ab != ba.map(Ordering::reverse) {
+        return Err(format!("partial_cmp not antisymmetric: partial_cmp({:?}, {:?}) = {:?}, but reversed = {:?}.",
+            a, b, ab, ba));
+    }
+
+    let merged = merge::<F>(a, b);
+    let expected = match ( eq(&merged, a), eq(&merged, b) ) {
+        ( true, true ) => Some(Ordering::Equal),
+        ( true, false ) => Some(Ordering::Greater),
+        ( false, true ) => Some(Ordering::Less),
+        ( false, false ) => None,
+    };
+    if ab != expected {
+        return Err(format!("partial_cmp disagrees with merge_in: partial_cmp({:?}, {:?}) = {:?}, but merge = {:?}.",
+            a, b, ab, merged));
+    }
+    Ok(())
+}
+
+fn absorption<F: Meet>(a: &F::Domain, b: &F::Domain, eq: &impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let a_ab = merge::<F>(a, &merge::<Dual<F>>(a, b));
+    if !eq(&a_ab, a) {
+        return Err(format!("Not absorbing: merge({:?}, meet({:?}, {:?})) = {:?}.", a, a, b, a_ab));
+    }
+    let a_ab = merge::<Dual<F>>(a, &merge::<F>(a, b));
+    if !eq(&a_ab, a) {
+        return Err(format!("Not absorbing: meet({:?}, merge({:?}, {:?})) = {:?}.", a, a, b, a_ab));
+    }
+    Ok(())
+}
+
+// QUICKCHECK HARNESS //
+// Runs the checks above on samples from `quickcheck`. Enable the
+// `quickcheck` feature to use these from your own tests.
+
+/// Samples per batch, kept small as `check_all` is cubic in it.
+#[cfg(feature = "quickcheck")]
+const GEN_SIZE: usize = 8;
+#[cfg(feature = "quickcheck")]
+const ITERATIONS: usize = 100;
+
+/// Checks the laws on batches of samples built from arbitrary seeds. Use this
+/// when samples are only valid together, e.g. states of replicas sharing a
+/// history.
+#[cfg(feature = "quickcheck")]
+pub fn check_laws_batch<F: Merge, A: Arbitrary>(make: impl Fn(Vec<A>) -> Vec<F::Domain>) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    check_laws_batch_by::<F, A>(make, PartialEq::eq)
+}
+
+/// `check_laws_batch`, comparing values with `eq` instead of `==`.
+#[cfg(feature = "quickcheck")]
+pub fn check_laws_batch_by<F: Merge, A: Arbitrary>(make: impl Fn(Vec<A>) -> Vec<F::Domain>, eq: impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let mut gen = Gen::new(GEN_SIZE);
+    for _ in 0..ITERATIONS {
+        let samples = make(Vec::arbitrary(&mut gen));
+        check_all_by::<F>(&samples, &eq)?;
+    }
+    Ok(())
+}
+
+/// `check_laws_batch_by` with the full lattice laws, join and meet.
+#[cfg(feature = "quickcheck")]
+pub fn check_lattice_laws_batch_by<F: Meet, A: Arbitrary>(make: impl Fn(Vec<A>) -> Vec<F::Domain>, eq: impl Fn(&F::Domain, &F::Domain) -> bool) -> Result<(), String>
+where
+    F::Domain: Clone + Debug,
+{
+    let mut gen = Gen::new(GEN_SIZE);
+    for _ in 0..ITERATIONS {
+        let samples = make(Vec::arbitrary(&mut gen));
+        check_all_lattice_by::<F>(&samples, &eq)?;
+    }
+    Ok(())
+}
+
+/// Checks the laws on samples each built from one arbitrary seed.
+#[cfg(feature = "quickcheck")]
+pub fn check_laws<F: Merge, A: Arbitrary>(make: impl Fn(A) -> F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    check_laws_batch::<F, A>(|seeds| seeds.into_iter().map(&make).collect())
+}
+
+/// Checks the laws on arbitrary samples.
+#[cfg(feature = "quickcheck")]
+pub fn check_laws_arbitrary<F: Merge>() -> Result<(), String>
+where
+    F::Domain: Arbitrary + PartialEq + Debug,
+{
+    check_laws::<F, F::Domain>(|x| x)
+}
+
+/// Checks the full lattice laws, join and meet, on samples each built from
+/// one arbitrary seed.
+#[cfg(feature = "quickcheck")]
+pub fn check_lattice_laws<F: Meet, A: Arbitrary>(make: impl Fn(A) -> F::Domain) -> Result<(), String>
+where
+    F::Domain: Clone + PartialEq + Debug,
+{
+    let mut gen = Gen::new(GEN_SIZE);
+    for _ in 0..ITERATIONS {
+        let samples: Vec<F::Domain> = Vec::arbitrary(&mut gen).into_iter().map(&make).collect();
+        check_all_lattice::<F>(&samples)?;
+    }
+    Ok(())
+}
+
+/// Checks the full lattice laws on arbitrary samples.
+#[cfg(feature = "quickcheck")]
+pub fn check_lattice_laws_arbitrary<F: Meet>() -> Result<(), String>
+where
+    F::Domain: Arbitrary + PartialEq + Debug,
+{
+    check_lattice_laws::<F, F::Domain>(|x| x)
+}
+
+/// Checks `merge_in_ref` on every pair of arbitrary samples.
+#[cfg(feature = "quickcheck")]
+pub fn check_merge_ref_arbitrary<F: MergeRef>() -> Result<(), String>
+where
+    F::Domain: Arbitrary + PartialEq + Debug,
+{
+    let mut gen = Gen::new(GEN_SIZE);
+    for _ in 0..ITERATIONS {
+        let samples: Vec<F::Domain> = Vec::arbitrary(&mut gen);
+        for a in &samples {
+            for b in &samples {
+                check_merge_ref::<F>(a, b)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks `merge_all` on every suffix of arbitrary samples.
+#[cfg(feature = "quickcheck")]
+pub fn check_merge_all_arbitrary<F: Merge>() -> Result<(), String>
+where
+    F::Domain: Arbitrary + PartialEq + Debug,
+{
+    let mut gen = Gen::new(GEN_SIZE);
+    for _ in 0..ITERATIONS {
+        let samples: Vec<F::Domain> = Vec::arbitrary(&mut gen);
+        for ( i, a ) in samples.iter().enumerate() {
+            check_merge_all::<F>(a, &samples[i..])?;
+        }
+    }
+    Ok(())
+}
+
+
+// GENERATORS //
+// `Arbitrary` for the built-in domains, each built from a random history of
+// operations so it is a state replicas could really reach. `MvRegister` has
+// none: its clocks are only unique within one history, so build samples with
+// `check_laws_batch` instead.
+
+#[cfg(feature = "quickcheck")]
+impl <T: Arbitrary + Ord> Arbitrary for SortedVec<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into()
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl Arbitrary for BitSet {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<u8>::arbitrary(g).into_iter().map(usize::from).collect()
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <K: Arbitrary + Hash + Eq> Arbitrary for VClock<K> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        HashMap::<K, u64>::arbitrary(g).into()
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <T: Arbitrary + Ord, Ts: Arbitrary + Ord> Arbitrary for LwwRegister<T, Ts> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        LwwRegister::new(Ts::arbitrary(g), T::arbitrary(g))
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <T: Arbitrary + Hash + Eq, K: Arbitrary + Hash + Eq> Arbitrary for OrSet<T, K> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut set = OrSet::new();
+        for ( node, value, insert ) in Vec::<( K, T, bool )>::arbitrary(g) {
+            if insert {
+                set.insert(node, value);
+            }
+            else {
+                set.remove(&value);
+            }
+        }
+        set
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <K: Arbitrary + Hash + Eq> Arbitrary for GCounter<K> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut counter = GCounter::new();
+        for ( replica, n ) in Vec::<( K, u8 )>::arbitrary(g) {
+            counter.increment(replica, n.into());
+        }
+        counter
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <K: Arbitrary + Hash + Eq> Arbitrary for PnCounter<K> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut counter = PnCounter::new();
+        for ( replica, n, inc ) in Vec::<( K, u8, bool )>::arbitrary(g) {
+            if inc {
+                counter.increment(replica, n.into());
+            }
+            else {
+                counter.decrement(replica, n.into());
+            }
+        }
+        counter
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <T: Arbitrary + Hash + Eq> Arbitrary for TwoPhaseSet<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut set = TwoPhaseSet::new();
+        for ( value, insert ) in Vec::<( T, bool )>::arbitrary(g) {
+            if insert {
+                set.insert(value);
+            }
+            else {
+                set.remove(value);
+            }
+        }
+        set
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <T: Arbitrary + Hash + Eq, Ts: Arbitrary + Ord, B: Bias + Clone + 'static> Arbitrary for LwwElementSet<T, Ts, B> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut set = LwwElementSet::new();
+        for ( value, ts, insert ) in Vec::<( T, Ts, bool )>::arbitrary(g) {
+            if insert {
+                set.insert(value, ts);
+            }
+            else {
+                set.remove(value, ts);
+            }
+        }
+        set
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <const P: u8> Arbitrary for HyperLogLog<P> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut hll = HyperLogLog::new();
+        for item in Vec::<u64>::arbitrary(g) {
+            hll.insert(&item);
+        }
+        hll
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <const BITS: usize, const K: u32> Arbitrary for BloomFilter<BITS, K> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut bloom = BloomFilter::new();
+        for item in Vec::<u64>::arbitrary(g) {
+            bloom.insert(&item);
+        }
+        bloom
+    }
+}
+
+
+fn merge<F: Merge>(a: &F::Domain, b: &F::Domain) -> F::Domain
+where
+    F::Domain: Clone,
+{
+    let mut val = a.clone();
+    F::merge_in(&mut val, b.clone());
+    val
+}
@@ -1,6 +1,10 @@
 #![feature(type_alias_impl_trait)]
 // #![feature(impl_trait_in_bindings)]
-#![feature(drain_filter)]
+#![feature(impl_trait_in_assoc_type)]
+
+pub mod collections;
+
+pub mod laws;
 
 pub mod merge;
 
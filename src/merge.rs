@@ -1,11 +1,15 @@
-use std::hash::Hash;
-use std::collections::{ BTreeMap, BTreeSet, HashMap, HashSet };
+use std::hash::{ Hash, Hasher };
+use std::collections::{ HashMap, HashSet };
 use std::collections::hash_map;
-use std::collections::btree_map;
 use std::iter::Extend;
 
 use std::cmp::Ordering;
 
+use crate::collections::{ MapLike, SetLike };
+
+#[cfg(feature = "derive")]
+pub use spinach_derive::Merge;
+
 /// Merge trait.
 pub trait Merge {
     type Domain;
@@ -14,427 +18,2519 @@ pub trait Merge {
     fn merge_in(val: &mut Self::Domain, other: Self::Domain);
 
     fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering>;
+
+    /// Merges a whole batch into `val`. Override this when a batch can be
+    /// merged faster than one value at a time.
+    fn merge_all(val: &mut Self::Domain, others: impl IntoIterator<Item = Self::Domain>) {
+        for other in others {
+            Self::merge_in(val, other);
+        }
+    }
 }
 
+/// Merge which also reports what changed, for building delta-state pipelines.
+pub trait DeltaMerge: Merge {
+    /// Merges `other` into `val`, returning a delta containing the new
+    /// information, or `None` if `val` did not change. Merging the delta into
+    /// the old `val` gives the new `val`.
+    fn merge_in_delta(val: &mut Self::Domain, other: Self::Domain) -> Option<Self::Domain>;
+}
 
+/// Merge which can merge in a borrowed value, cloning only what it keeps.
+pub trait MergeRef: Merge {
+    /// Same result as `merge_in(val, other.clone())`.
+    fn merge_in_ref(val: &mut Self::Domain, other: &Self::Domain);
+}
 
-// ORD MERGES //
+/// Merge with a least element, the identity of `merge_in`.
+pub trait Bottom: Merge {
+    fn bottom() -> Self::Domain;
+}
 
-pub struct Max<T: Ord> {
-    _phantom: std::marker::PhantomData<T>,
+/// Merge with a greatest element, which absorbs anything merged into it.
+pub trait Top: Merge {
+    fn top() -> Self::Domain;
 }
-impl <T: Ord> Merge for Max<T> {
-    type Domain = T;
 
-    fn merge_in(val: &mut T, other: T) {
-        if *val < other {
-            *val = other;
+/// Merge which is a full lattice, with a meet (greatest lower bound) as well
+/// as a join (`merge_in`).
+pub trait Meet: Merge {
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain);
+
+    /// Meets a whole batch into `val`, like `Merge::merge_all`.
+    fn meet_all(val: &mut Self::Domain, others: impl IntoIterator<Item = Self::Domain>) {
+        for other in others {
+            Self::meet_in(val, other);
         }
     }
+}
 
-    fn partial_cmp(val: &T, other: &T) -> Option<Ordering> {
-        val.partial_cmp(other)
-    }
+/// Merge with widening and narrowing operators, for fixpoint iteration over
+/// lattices with infinite ascending chains (abstract interpretation).
+pub trait Widen: Merge {
+    /// Like `merge_in`, but jumps past unstable bounds so that repeated
+    /// widening always stabilizes.
+    fn widen_in(val: &mut Self::Domain, other: Self::Domain);
+
+    /// Refines a widened `val` towards a smaller `other`, only undoing jumps
+    /// made by widening, so that repeated narrowing also stabilizes.
+    fn narrow_in(val: &mut Self::Domain, other: Self::Domain);
 }
 
-pub struct Min<T: Ord> {
-    _phantom: std::marker::PhantomData<T>,
+
+
+// DUAL MERGE //
+
+/// Flips the order of a lattice, swapping its join and meet.
+pub struct Dual<F: Meet> {
+    _phantom: std::marker::PhantomData<F>,
 }
-impl <T: Ord> Merge for Min<T> {
-    type Domain = T;
+impl <F: Meet> Merge for Dual<F> {
+    type Domain = F::Domain;
 
-    fn merge_in(val: &mut T, other: T) {
-        if *val > other {
-            *val = other;
-        }
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        F::meet_in(val, other);
     }
 
-    fn partial_cmp(val: &T, other: &T) -> Option<Ordering> {
-        val.partial_cmp(other).map(|ord| ord.reverse())
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        F::partial_cmp(val, other).map(Ordering::reverse)
+    }
+
+    fn merge_all(val: &mut Self::Domain, others: impl IntoIterator<Item = Self::Domain>) {
+        F::meet_all(val, others);
     }
 }
+impl <F: Meet> Meet for Dual<F> {
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+        F::merge_in(val, other);
+    }
 
-// SET MERGES //
+    fn meet_all(val: &mut Self::Domain, others: impl IntoIterator<Item = Self::Domain>) {
+        F::merge_all(val, others);
+    }
+}
+impl <F: Meet + Top> Bottom for Dual<F> {
+    fn bottom() -> Self::Domain {
+        F::top()
+    }
+}
+impl <F: Meet + Bottom> Top for Dual<F> {
+    fn top() -> Self::Domain {
+        F::bottom()
+    }
+}
 
-pub struct Union<T> {
-    _phantom: std::marker::PhantomData<T>,
+
+
+// BOUND ADAPTERS //
+
+/// Adds a new bottom, `None`, below all of `F`'s values. `None` is the
+/// identity, so any lattice can be given a starting (empty) state.
+pub struct WithBot<F: Merge> {
+    _phantom: std::marker::PhantomData<F>,
 }
-impl <T: Eq + Hash> Merge for Union<HashSet<T>> {
-    type Domain = HashSet<T>;
+impl <F: Merge> Merge for WithBot<F> {
+    type Domain = Option<F::Domain>;
 
-    fn merge_in(val: &mut HashSet<T>, other: HashSet<T>) {
-        val.extend(other);
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        match ( val.as_mut(), other ) {
+            ( _, None ) => {},
+            ( None, other ) => *val = other,
+            ( Some(val), Some(other) ) => F::merge_in(val, other),
+        }
     }
 
-    fn partial_cmp(val: &HashSet<T>, other: &HashSet<T>) -> Option<Ordering> {
-        let s = val.union(other).count();
-        if s != val.len() && s != other.len() {
-            None
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        match ( val, other ) {
+            ( None, None ) => Some(Ordering::Equal),
+            ( None, Some(_) ) => Some(Ordering::Less),
+            ( Some(_), None ) => Some(Ordering::Greater),
+            ( Some(val), Some(other) ) => F::partial_cmp(val, other),
         }
-        else if s == val.len() {
-            if s == other.len() {
-                Some(Ordering::Equal)
-            }
-            else {
-                Some(Ordering::Greater)
-            }
+    }
+}
+impl <F: DeltaMerge> DeltaMerge for WithBot<F>
+where
+    F::Domain: Clone,
+{
+    fn merge_in_delta(val: &mut Self::Domain, other: Self::Domain) -> Option<Self::Domain> {
+        match ( val.as_mut(), other ) {
+            ( _, None ) => None,
+            ( None, other ) => {
+                *val = other.clone();
+                Some(other)
+            },
+            ( Some(val), Some(other) ) => F::merge_in_delta(val, other).map(Some),
         }
-        else {
-            Some(Ordering::Less)
+    }
+}
+impl <F: Meet> Meet for WithBot<F> {
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+        match ( val.as_mut(), other ) {
+            ( None, _ ) => {},
+            ( _, None ) => *val = None,
+            ( Some(val), Some(other) ) => F::meet_in(val, other),
         }
     }
 }
-impl <T: Eq + Ord> Merge for Union<BTreeSet<T>> {
-    type Domain = BTreeSet<T>;
+impl <F: Widen> Widen for WithBot<F> {
+    fn widen_in(val: &mut Self::Domain, other: Self::Domain) {
+        match ( val.as_mut(), other ) {
+            ( _, None ) => {},
+            ( None, other ) => *val = other,
+            ( Some(val), Some(other) ) => F::widen_in(val, other),
+        }
+    }
 
-    fn merge_in(val: &mut BTreeSet<T>, other: BTreeSet<T>) {
-        val.extend(other);
+    fn narrow_in(val: &mut Self::Domain, other: Self::Domain) {
+        match ( val.as_mut(), other ) {
+            ( _, None ) => *val = None,
+            ( None, _ ) => {},
+            ( Some(val), Some(other) ) => F::narrow_in(val, other),
+        }
+    }
+}
+impl <F: Merge> Bottom for WithBot<F> {
+    fn bottom() -> Self::Domain {
+        None
     }
+}
+impl <F: Top> Top for WithBot<F> {
+    fn top() -> Self::Domain {
+        Some(F::top())
+    }
+}
 
-    fn partial_cmp(val: &BTreeSet<T>, other: &BTreeSet<T>) -> Option<Ordering> {
-        let s = val.union(other).count();
-        if s != val.len() && s != other.len() {
-            None
+/// Adds a new top, `None`, above all of `F`'s values. `None` absorbs
+/// everything merged into it, so it can mark a poisoned state.
+pub struct WithTop<F: Merge> {
+    _phantom: std::marker::PhantomData<F>,
+}
+impl <F: Merge> Merge for WithTop<F> {
+    type Domain = Option<F::Domain>;
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        match ( val.as_mut(), other ) {
+            ( None, _ ) => {},
+            ( _, None ) => *val = None,
+            ( Some(val), Some(other) ) => F::merge_in(val, other),
         }
-        else if s == val.len() {
-            if s == other.len() {
-                Some(Ordering::Equal)
-            }
-            else {
-                Some(Ordering::Greater)
-            }
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        match ( val, other ) {
+            ( None, None ) => Some(Ordering::Equal),
+            ( None, Some(_) ) => Some(Ordering::Greater),
+            ( Some(_), None ) => Some(Ordering::Less),
+            ( Some(val), Some(other) ) => F::partial_cmp(val, other),
         }
-        else {
-            Some(Ordering::Less)
+    }
+}
+impl <F: Meet> Meet for WithTop<F> {
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+        match ( val.as_mut(), other ) {
+            ( _, None ) => {},
+            ( None, other ) => *val = other,
+            ( Some(val), Some(other) ) => F::meet_in(val, other),
         }
     }
 }
+impl <F: Bottom> Bottom for WithTop<F> {
+    fn bottom() -> Self::Domain {
+        Some(F::bottom())
+    }
+}
+impl <F: Merge> Top for WithTop<F> {
+    fn top() -> Self::Domain {
+        None
+    }
+}
+
+
+
+// ORD MERGES //
 
-pub struct Intersect<T> {
+pub struct Max<T: Ord> {
     _phantom: std::marker::PhantomData<T>,
 }
-impl <T: Eq + Hash> Merge for Intersect<HashSet<T>> {
-    type Domain = HashSet<T>;
+impl <T: Ord> Merge for Max<T> {
+    type Domain = T;
 
-    fn merge_in(val: &mut HashSet<T>, other: HashSet<T>) {
-        val.retain(|x| other.contains(x));
+    fn merge_in(val: &mut T, other: T) {
+        if *val < other {
+            *val = other;
+        }
     }
 
-    fn partial_cmp(val: &HashSet<T>, other: &HashSet<T>) -> Option<Ordering> {
-        let s = val.intersection(other).count();
-        if s != val.len() && s != other.len() {
-            None
-        }
-        else if s == val.len() {
-            if s == other.len() {
-                Some(Ordering::Equal)
-            }
-            else {
-                Some(Ordering::Greater)
-            }
+    fn partial_cmp(val: &T, other: &T) -> Option<Ordering> {
+        val.partial_cmp(other)
+    }
+}
+impl <T: Ord + Clone> DeltaMerge for Max<T> {
+    fn merge_in_delta(val: &mut T, other: T) -> Option<T> {
+        if *val < other {
+            *val = other.clone();
+            Some(other)
         }
         else {
-            Some(Ordering::Less)
+            None
         }
     }
 }
-impl <T: Eq + Ord> Merge for Intersect<BTreeSet<T>> {
-    type Domain = BTreeSet<T>;
-
-    fn merge_in(val: &mut BTreeSet<T>, other: BTreeSet<T>) {
-        // Not so ergonomic nor efficient.
-        *val = other.into_iter()
-            .filter(|x| val.contains(x))
-            .collect();
+impl <T: Ord + Clone> MergeRef for Max<T> {
+    fn merge_in_ref(val: &mut T, other: &T) {
+        if *val < *other {
+            *val = other.clone();
+        }
     }
+}
 
-    fn partial_cmp(val: &BTreeSet<T>, other: &BTreeSet<T>) -> Option<Ordering> {
-        let s = val.intersection(other).count();
-        if s != val.len() && s != other.len() {
-            None
+impl <T: Ord> Meet for Max<T> {
+    fn meet_in(val: &mut T, other: T) {
+        if *val > other {
+            *val = other;
         }
-        else if s == val.len() {
-            if s == other.len() {
-                Some(Ordering::Equal)
-            }
-            else {
-                Some(Ordering::Greater)
-            }
+    }
+}
+
+pub type Min<T> = Dual<Max<T>>;
+impl <T: Ord + Clone> DeltaMerge for Min<T> {
+    fn merge_in_delta(val: &mut T, other: T) -> Option<T> {
+        if *val > other {
+            *val = other.clone();
+            Some(other)
         }
         else {
-            Some(Ordering::Less)
+            None
         }
     }
 }
-
-// MAP MERGES //
-
-pub struct MapUnion<T> {
-    _phantom: std::marker::PhantomData<T>,
-}
-
-impl <K, F> Merge for MapUnion<HashMap<K, F>>
-where
-    K: Hash + Eq,
-    F: Merge,
-{
-    type Domain = HashMap<K, <F as Merge>::Domain>;
-
-    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
-        for (k, v) in other {
-            match val.entry(k) {
-                hash_map::Entry::Occupied(mut kv) => {
-                    F::merge_in(kv.get_mut(), v);
-                },
-                hash_map::Entry::Vacant(kv) => {
-                    kv.insert(v);
-                },
-            }
+impl <T: Ord + Clone> MergeRef for Min<T> {
+    fn merge_in_ref(val: &mut T, other: &T) {
+        if *val > *other {
+            *val = other.clone();
         }
     }
+}
 
-    // TODO: these are awful looking, and also need testing. Could use helper method.
-    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
-        // Ordering::Equal OR Ordering::Greater
-        if val.len() >= other.len() {
-            let mut result = None;
-            for (k, other_val) in other {
-                match val.get(k) {
-                    Some(val_val) => {
-                        let cmp = F::partial_cmp(val_val, other_val);
-                        match cmp {
-                            Some(cmp) => {
-                                if result.get_or_insert(cmp) != &cmp {
-                                    return None;
-                                }
-                            },
-                            None => return None,
-                        }
-                    },
-                    None => return None,
+// Bounds for `Max` (and so `Min`) over types with known extremes.
+macro_rules! ord_bounds {
+    ( $( $t:ty: $min:expr, $max:expr; )+ ) => {
+        $(
+            impl Bottom for Max<$t> {
+                fn bottom() -> $t {
+                    $min
                 }
             }
-            if None == result {
-                return Some(Ordering::Equal);
-            }
-            else {
-                return Some(Ordering::Greater);
-            }
-        }
-        // Ordering::Less
-        else {
-            for (k, val_val) in val {
-                match other.get(k) {
-                    Some(other_val) => {
-                        let cmp = F::partial_cmp(val_val, other_val);
-                        if Some(Ordering::Less) != cmp {
-                            return None;
-                        }
-                    },
-                    None => return None,
+            impl Top for Max<$t> {
+                fn top() -> $t {
+                    $max
                 }
             }
-            return Some(Ordering::Less);
-        }
+        )+
+    };
+}
+ord_bounds! {
+    u8: u8::MIN, u8::MAX;
+    u16: u16::MIN, u16::MAX;
+    u32: u32::MIN, u32::MAX;
+    u64: u64::MIN, u64::MAX;
+    u128: u128::MIN, u128::MAX;
+    usize: usize::MIN, usize::MAX;
+    i8: i8::MIN, i8::MAX;
+    i16: i16::MIN, i16::MAX;
+    i32: i32::MIN, i32::MAX;
+    i64: i64::MIN, i64::MAX;
+    i128: i128::MIN, i128::MAX;
+    isize: isize::MIN, isize::MAX;
+    bool: false, true;
+    char: '\0', char::MAX;
+}
+// Strings have a least element but no greatest.
+impl Bottom for Max<String> {
+    fn bottom() -> String {
+        String::new()
+    }
+}
+impl <'a> Bottom for Max<&'a str> {
+    fn bottom() -> &'a str {
+        ""
     }
 }
 
-impl <K, F> Merge for MapUnion<BTreeMap<K, F>>
-where
-    K: Ord + Eq,
-    F: Merge,
-{
-    type Domain = BTreeMap<K, <F as Merge>::Domain>;
+// FLOAT MERGES //
 
-    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
-        for (k, v) in other {
-            match val.entry(k) {
-                btree_map::Entry::Occupied(mut kv) => {
-                    F::merge_in(kv.get_mut(), v);
-                },
-                btree_map::Entry::Vacant(kv) => {
-                    kv.insert(v);
-                },
-            }
-        }
-    }
+/// Floating point types, for `FloatMax` and `FloatMin`.
+pub trait Float: Copy + PartialOrd {
+    const NAN: Self;
+    const INFINITY: Self;
+    const NEG_INFINITY: Self;
+    /// Least value in the IEEE total order, a negative NaN.
+    const TOTAL_MIN: Self;
+    /// Greatest value in the IEEE total order, a positive NaN.
+    const TOTAL_MAX: Self;
 
-    // TODO: these are awful looking, and also need testing. Could use helper method.
-    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
-        // Ordering::Equal OR Ordering::Greater
-        if val.len() >= other.len() {
-            let mut result = None;
-            for (k, other_val) in other {
-                match val.get(k) {
-                    Some(val_val) => {
-                        let cmp = F::partial_cmp(val_val, other_val);
-                        match cmp {
-                            Some(cmp) => {
-                                if result.get_or_insert(cmp) != &cmp {
-                                    return None;
-                                }
-                            },
-                            None => return None,
-                        }
-                    },
-                    None => return None,
+    fn is_nan(self) -> bool;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+macro_rules! float_impl {
+    ( $( $t:ident: $bits:ty; )+ ) => {
+        $(
+            impl Float for $t {
+                const NAN: Self = $t::NAN;
+                const INFINITY: Self = $t::INFINITY;
+                const NEG_INFINITY: Self = $t::NEG_INFINITY;
+                const TOTAL_MIN: Self = $t::from_bits(<$bits>::MAX);
+                const TOTAL_MAX: Self = $t::from_bits(<$bits>::MAX >> 1);
+
+                fn is_nan(self) -> bool {
+                    $t::is_nan(self)
                 }
-            }
-            if None == result {
-                return Some(Ordering::Equal);
-            }
-            else {
-                return Some(Ordering::Greater);
-            }
-        }
-        // Ordering::Less
-        else {
-            for (k, val_val) in val {
-                match other.get(k) {
-                    Some(other_val) => {
-                        let cmp = F::partial_cmp(val_val, other_val);
-                        if Some(Ordering::Less) != cmp {
-                            return None;
-                        }
-                    },
-                    None => return None,
+
+                fn total_cmp(&self, other: &Self) -> Ordering {
+                    $t::total_cmp(self, other)
                 }
             }
-            return Some(Ordering::Less);
-        }
-    }
-}
-
-// pub struct MapIntersection<T> {
-//     _phantom: std::marker::PhantomData<T>,
-// }
-// impl <K, F> Merge for MapIntersection<HashMap<K, F>>
-// where
-//     K: Eq + Hash,
-//     F: Merge,
-// {
-//     type Domain = HashMap<K, <F as Merge>::Domain>;
-
-//     fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
-//         todo!("this is broken.");
-//         for (k, v) in other {
-//             val.entry(k).and_modify(|v0| F::merge_in(v0, v));
-//         }
-//     }
-
-//     fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
-//         todo!("this is broken.");
-//         // Ordering::Equal OR Ordering::Less
-//         if val.len() >= other.len() {
-//             let mut result = None;
-//             for (k, other_val) in other {
-//                 match val.get(k) {
-//                     Some(val_val) => {
-//                         let cmp = F::partial_cmp(&val_val, other_val);
-//                         match cmp {
-//                             Some(cmp) => {
-//                                 if result.get_or_insert(cmp) != &cmp {
-//                                     return None;
-//                                 }
-//                             },
-//                             None => return None,
-//                         }
-//                     },
-//                     None => return None,
-//                 }
-//             }
-//             if None == result {
-//                 return Some(Ordering::Equal);
-//             }
-//             else {
-//                 return Some(Ordering::Less);
-//             }
-//         }
-//         // Ordering::Greater
-//         else {
-//             for (k, val_val) in val {
-//                 match other.get(k) {
-//                     Some(other_val) => {
-//                         let cmp = F::partial_cmp(&val_val, other_val);
-//                         if Some(Ordering::Greater) != cmp {
-//                             return None;
-//                         }
-//                     },
-//                     None => return None,
-//                 }
-//             }
-//             return Some(Ordering::Greater);
-//         }
-//     }
-// }
+        )+
+    };
+}
+float_impl! {
+    f32: u32;
+    f64: u64;
+}
 
-pub struct DominatingPair<AF, BF>
-where
-    AF: Merge,
-    BF: Merge,
-{
-    _phantom: std::marker::PhantomData<(AF, BF)>,
+/// How a float lattice orders NaN, making floats totally ordered.
+///
+/// The float lattices break ties in `cmp` (between NaNs, or `-0.0` and
+/// `+0.0`) with `total_cmp`, so that replicas agree on the exact bits.
+/// `least` and `greatest` are the least and greatest values in that order.
+pub trait NanPolicy {
+    fn cmp<T: Float>(a: &T, b: &T) -> Ordering;
+    fn least<T: Float>() -> T;
+    fn greatest<T: Float>() -> T;
 }
 
-impl <AF, BF> Merge for DominatingPair<AF, BF>
-where
-    AF: Merge,
-    BF: Merge,
-{
-    type Domain = (<AF as Merge>::Domain, <BF as Merge>::Domain);
+/// `P`'s order, with ties broken by `total_cmp`.
+fn float_cmp<T: Float, P: NanPolicy>(a: &T, b: &T) -> Ordering {
+    P::cmp(a, b).then_with(|| a.total_cmp(b))
+}
 
-    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
-        let cmp = AF::partial_cmp(&val.0, &other.0);
-        match cmp {
-            None => {
-                AF::merge_in(&mut val.0, other.0);
-                BF::merge_in(&mut val.1, other.1);
-            },
-            Some(Ordering::Equal) => {
-                BF::merge_in(&mut val.1, other.1);
-            },
-            Some(Ordering::Less) => {
-                *val = other;
-            },
-            Some(Ordering::Greater) => {},
+/// All NaNs are equal, and less than every other value.
+pub struct NanIsBottom;
+impl NanPolicy for NanIsBottom {
+    fn cmp<T: Float>(a: &T, b: &T) -> Ordering {
+        match ( a.is_nan(), b.is_nan() ) {
+            ( true, true ) => Ordering::Equal,
+            ( true, false ) => Ordering::Less,
+            ( false, true ) => Ordering::Greater,
+            ( false, false ) => a.partial_cmp(b).unwrap(),
         }
     }
 
-    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
-        AF::partial_cmp(&val.0, &other.0).or_else(|| BF::partial_cmp(&val.1, &other.1))
+    fn least<T: Float>() -> T {
+        T::TOTAL_MIN
+    }
+
+    fn greatest<T: Float>() -> T {
+        T::INFINITY
     }
 }
 
+/// All NaNs are equal, and greater than every other value.
+pub struct NanIsTop;
+impl NanPolicy for NanIsTop {
+    fn cmp<T: Float>(a: &T, b: &T) -> Ordering {
+        match ( a.is_nan(), b.is_nan() ) {
+            ( true, true ) => Ordering::Equal,
+            ( true, false ) => Ordering::Greater,
+            ( false, true ) => Ordering::Less,
+            ( false, false ) => a.partial_cmp(b).unwrap(),
+        }
+    }
+
+    fn least<T: Float>() -> T {
+        T::NEG_INFINITY
+    }
 
+    fn greatest<T: Float>() -> T {
+        T::TOTAL_MAX
+    }
+}
 
+/// The IEEE 754 `totalOrder`: negative NaNs are least, positive NaNs are
+/// greatest, and `-0.0 < +0.0`.
+pub struct TotalOrder;
+impl NanPolicy for TotalOrder {
+    fn cmp<T: Float>(a: &T, b: &T) -> Ordering {
+        a.total_cmp(b)
+    }
 
+    fn least<T: Float>() -> T {
+        T::TOTAL_MIN
+    }
 
+    fn greatest<T: Float>() -> T {
+        T::TOTAL_MAX
+    }
+}
 
+/// `Max` for floats, ordering NaN according to `P`.
+pub struct FloatMax<T: Float, P: NanPolicy> {
+    _phantom: std::marker::PhantomData<(T, P)>,
+}
+impl <T: Float, P: NanPolicy> Merge for FloatMax<T, P> {
+    type Domain = T;
 
-// Mingwei's weird semilattice.
-// Merge is defined as, given signed integers A and B, take the value in the
-// range [A, B] (or [B, A]) which is closest to zero.
-// (Note that in general this will be A, B, or zero).
-pub struct RangeToZeroI32;
-impl Merge for RangeToZeroI32 {
-    type Domain = i32;
+    fn merge_in(val: &mut T, other: T) {
+        if Ordering::Less == float_cmp::<T, P>(val, &other) {
+            *val = other;
+        }
+    }
 
-    fn merge_in(val: &mut i32, other: i32) {
-        if val.signum() != other.signum() {
-            *val = 0;
+    fn partial_cmp(val: &T, other: &T) -> Option<Ordering> {
+        Some(float_cmp::<T, P>(val, other))
+    }
+}
+impl <T: Float, P: NanPolicy> DeltaMerge for FloatMax<T, P> {
+    fn merge_in_delta(val: &mut T, other: T) -> Option<T> {
+        if Ordering::Less == float_cmp::<T, P>(val, &other) {
+            *val = other;
+            Some(other)
+        }
+        else {
+            None
         }
-        else if val.abs() > other.abs() {
-            *val = other
+    }
+}
+impl <T: Float, P: NanPolicy> Meet for FloatMax<T, P> {
+    fn meet_in(val: &mut T, other: T) {
+        if Ordering::Greater == float_cmp::<T, P>(val, &other) {
+            *val = other;
         }
     }
+}
+impl <T: Float, P: NanPolicy> Bottom for FloatMax<T, P> {
+    fn bottom() -> T {
+        P::least()
+    }
+}
+impl <T: Float, P: NanPolicy> Top for FloatMax<T, P> {
+    fn top() -> T {
+        P::greatest()
+    }
+}
 
-    fn partial_cmp(val: &i32, other: &i32) -> Option<Ordering> {
-        if val.signum() != other.signum() {
-            None
+/// `Min` for floats, ordering NaN according to `P` (before flipping).
+pub type FloatMin<T, P> = Dual<FloatMax<T, P>>;
+impl <T: Float, P: NanPolicy> DeltaMerge for FloatMin<T, P> {
+    fn merge_in_delta(val: &mut T, other: T) -> Option<T> {
+        if Ordering::Greater == float_cmp::<T, P>(val, &other) {
+            *val = other;
+            Some(other)
         }
         else {
-            let less = val.abs().cmp(&other.abs());
-            Some(less.reverse())
+            None
         }
     }
 }
+
+// BOOL MERGES //
+
+/// Boolean or, `false` until any `true` is merged in.
+pub struct Or;
+impl Merge for Or {
+    type Domain = bool;
+
+    fn merge_in(val: &mut bool, other: bool) {
+        *val |= other;
+    }
+
+    fn partial_cmp(val: &bool, other: &bool) -> Option<Ordering> {
+        Some(val.cmp(other))
+    }
+}
+impl DeltaMerge for Or {
+    fn merge_in_delta(val: &mut bool, other: bool) -> Option<bool> {
+        if !*val && other {
+            *val = true;
+            Some(true)
+        }
+        else {
+            None
+        }
+    }
+}
+impl Meet for Or {
+    fn meet_in(val: &mut bool, other: bool) {
+        *val &= other;
+    }
+}
+impl Bottom for Or {
+    fn bottom() -> bool {
+        false
+    }
+}
+impl Top for Or {
+    fn top() -> bool {
+        true
+    }
+}
+
+/// Boolean and, `true` until any `false` is merged in.
+pub type And = Dual<Or>;
+impl DeltaMerge for And {
+    fn merge_in_delta(val: &mut bool, other: bool) -> Option<bool> {
+        if *val && !other {
+            *val = false;
+            Some(false)
+        }
+        else {
+            None
+        }
+    }
+}
+
+// SET MERGES //
+
+pub struct Union<T> {
+    _phantom: std::marker::PhantomData<T>,
+}
+impl <S: SetLike> Merge for Union<S> {
+    type Domain = S;
+
+    fn merge_in(val: &mut S, mut other: S) {
+        // Insert the smaller set into the larger.
+        if val.len() < other.len() {
+            std::mem::swap(val, &mut other);
+        }
+        val.extend(other);
+    }
+
+    fn merge_all(val: &mut S, others: impl IntoIterator<Item = S>) {
+        val.extend_all(others.into_iter().collect());
+    }
+
+    fn partial_cmp(val: &S, other: &S) -> Option<Ordering> {
+        // Only the smaller set can be a subset of the other.
+        match val.len().cmp(&other.len()) {
+            Ordering::Less => val.is_subset(other).then_some(Ordering::Less),
+            Ordering::Greater => other.is_subset(val).then_some(Ordering::Greater),
+            Ordering::Equal => val.is_subset(other).then_some(Ordering::Equal),
+        }
+    }
+}
+impl <S: SetLike> Meet for Union<S> {
+    fn meet_in(val: &mut S, mut other: S) {
+        // Filter the smaller set by the larger.
+        if val.len() > other.len() {
+            std::mem::swap(val, &mut other);
+        }
+        val.retain(|x| other.contains(x));
+    }
+
+    // One pass over `val`, rather than one per set.
+    fn meet_all(val: &mut S, others: impl IntoIterator<Item = S>) {
+        let others: Vec<S> = others.into_iter().collect();
+        val.retain(|x| others.iter().all(|other| other.contains(x)));
+    }
+}
+impl <S: SetLike> DeltaMerge for Union<S>
+where
+    S::Item: Clone,
+{
+    fn merge_in_delta(val: &mut S, other: S) -> Option<S> {
+        let mut delta = S::default();
+        for x in other {
+            if !val.contains(&x) {
+                delta.insert(x);
+            }
+        }
+        if delta.is_empty() {
+            None
+        }
+        else {
+            val.extend(delta.iter().cloned());
+            Some(delta)
+        }
+    }
+}
+impl <S: SetLike> MergeRef for Union<S>
+where
+    S::Item: Clone,
+{
+    fn merge_in_ref(val: &mut S, other: &S) {
+        for x in other.iter() {
+            if !val.contains(x) {
+                val.insert(x.clone());
+            }
+        }
+    }
+}
+impl <S: SetLike> Bottom for Union<S> {
+    fn bottom() -> S {
+        S::default()
+    }
+}
+
+pub type Intersect<T> = Dual<Union<T>>;
+impl <S: SetLike + Clone> DeltaMerge for Intersect<S> {
+    // The delta is the whole (shrunken) set, as removals can't be expressed
+    // any other way.
+    fn merge_in_delta(val: &mut S, other: S) -> Option<S> {
+        let len = val.len();
+        Self::merge_in(val, other);
+        if len == val.len() {
+            None
+        }
+        else {
+            Some(val.clone())
+        }
+    }
+}
+impl <S: SetLike> MergeRef for Intersect<S> {
+    fn merge_in_ref(val: &mut S, other: &S) {
+        val.retain(|x| other.contains(x));
+    }
+}
+
+// COMPACT SET MERGES //
+// Flat set representations, much cheaper to merge than `HashSet` or
+// `BTreeSet` when elements are small.
+
+/// Set stored as a sorted, deduplicated `Vec`. Merges are linear passes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SortedVec<T: Ord> {
+    items: Vec<T>,
+}
+impl <T: Ord> SortedVec<T> {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+        }
+    }
+
+    /// Returns if `item` was newly inserted.
+    pub fn insert(&mut self, item: T) -> bool {
+        match self.items.binary_search(&item) {
+            Ok(_) => false,
+            Err(i) => {
+                self.items.insert(i, item);
+                true
+            },
+        }
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.items.binary_search(item).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+impl <T: Ord> Default for SortedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <T: Ord> From<Vec<T>> for SortedVec<T> {
+    fn from(mut items: Vec<T>) -> Self {
+        items.sort_unstable();
+        items.dedup();
+        Self {
+            items: items,
+        }
+    }
+}
+impl <T: Ord> std::iter::FromIterator<T> for SortedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<T>>().into()
+    }
+}
+impl <T: Ord> IntoIterator for SortedVec<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+impl <'a, T: Ord> IntoIterator for &'a SortedVec<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+/// If every item of sorted `a` is in sorted `b`.
+fn sorted_is_subset<T: Ord>(a: &[T], b: &[T]) -> bool {
+    if a.len() > b.len() {
+        return false;
+    }
+    let mut b = b.iter();
+    'outer: for x in a {
+        for y in &mut b {
+            match x.cmp(y) {
+                Ordering::Less => return false,
+                Ordering::Equal => continue 'outer,
+                Ordering::Greater => {},
+            }
+        }
+        return false;
+    }
+    true
+}
+
+impl <T: Ord> Merge for Union<SortedVec<T>> {
+    type Domain = SortedVec<T>;
+
+    fn merge_in(val: &mut SortedVec<T>, other: SortedVec<T>) {
+        // Disjoint runs just append.
+        match ( val.items.last(), other.items.first() ) {
+            ( _, None ) => return,
+            ( Some(last), Some(first) ) if last < first => {
+                val.items.extend(other.items);
+                return;
+            },
+            _ => {},
+        }
+
+        let mut merged = Vec::with_capacity(val.items.len() + other.items.len());
+        let mut a = std::mem::take(&mut val.items).into_iter().peekable();
+        let mut b = other.items.into_iter().peekable();
+        loop {
+            let next = match ( a.peek(), b.peek() ) {
+                ( Some(x), Some(y) ) => match x.cmp(y) {
+                    Ordering::Less => a.next(),
+                    Ordering::Greater => b.next(),
+                    Ordering::Equal => {
+                        b.next();
+                        a.next()
+                    },
+                },
+                ( Some(_), None ) => a.next(),
+                ( None, Some(_) ) => b.next(),
+                ( None, None ) => break,
+            };
+            merged.extend(next);
+        }
+        val.items = merged;
+    }
+
+    fn partial_cmp(val: &SortedVec<T>, other: &SortedVec<T>) -> Option<Ordering> {
+        match val.len().cmp(&other.len()) {
+            Ordering::Less => sorted_is_subset(&val.items, &other.items).then_some(Ordering::Less),
+            Ordering::Greater => sorted_is_subset(&other.items, &val.items).then_some(Ordering::Greater),
+            Ordering::Equal => (val.items == other.items).then_some(Ordering::Equal),
+        }
+    }
+}
+impl <T: Ord> Meet for Union<SortedVec<T>> {
+    fn meet_in(val: &mut SortedVec<T>, other: SortedVec<T>) {
+        let mut other = other.items.iter().peekable();
+        val.items.retain(|x| {
+            while other.next_if(|y| *y < x).is_some() {}
+            other.peek() == Some(&x)
+        });
+    }
+}
+impl <T: Ord + Clone> MergeRef for Union<SortedVec<T>> {
+    fn merge_in_ref(val: &mut SortedVec<T>, other: &SortedVec<T>) {
+        if !sorted_is_subset(&other.items, &val.items) {
+            Self::merge_in(val, other.clone());
+        }
+    }
+}
+impl <T: Ord> Bottom for Union<SortedVec<T>> {
+    fn bottom() -> SortedVec<T> {
+        SortedVec::new()
+    }
+}
+
+/// Set of `usize`s stored as a bitmap of `u64` words. Merges are
+/// word-parallel. Memory grows with the largest element, so only use this
+/// for dense ids.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    // Never has trailing zero words, so derived equality is set equality.
+    words: Vec<u64>,
+}
+impl BitSet {
+    pub fn new() -> Self {
+        Self {
+            words: Vec::new(),
+        }
+    }
+
+    /// Returns if `item` was newly inserted.
+    pub fn insert(&mut self, item: usize) -> bool {
+        let ( word, bit ) = ( item / 64, 1 << (item % 64) );
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let inserted = 0 == self.words[word] & bit;
+        self.words[word] |= bit;
+        inserted
+    }
+
+    /// Returns if `item` was present.
+    pub fn remove(&mut self, item: usize) -> bool {
+        let removed = self.contains(item);
+        if removed {
+            self.words[item / 64] &= !(1 << (item % 64));
+            self.trim();
+        }
+        removed
+    }
+
+    pub fn contains(&self, item: usize) -> bool {
+        self.words.get(item / 64)
+            .is_some_and(|word| 0 != word & (1 << (item % 64)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Iterates the elements in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter()
+            .enumerate()
+            .flat_map(|( i, &word )| (0..64)
+                .filter(move |bit| 0 != word & (1 << bit))
+                .map(move |bit| 64 * i + bit))
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    fn trim(&mut self) {
+        while let Some(0) = self.words.last() {
+            self.words.pop();
+        }
+    }
+}
+impl std::iter::FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for item in iter {
+            set.insert(item);
+        }
+        set
+    }
+}
+
+impl Merge for Union<BitSet> {
+    type Domain = BitSet;
+
+    fn merge_in(val: &mut BitSet, other: BitSet) {
+        Self::merge_in_ref(val, &other);
+    }
+
+    fn partial_cmp(val: &BitSet, other: &BitSet) -> Option<Ordering> {
+        let mut val_extra = val.words.len() > other.words.len();
+        let mut other_extra = val.words.len() < other.words.len();
+        for ( a, b ) in val.words.iter().zip(&other.words) {
+            val_extra |= 0 != a & !b;
+            other_extra |= 0 != b & !a;
+        }
+        match ( val_extra, other_extra ) {
+            ( false, false ) => Some(Ordering::Equal),
+            ( true, false ) => Some(Ordering::Greater),
+            ( false, true ) => Some(Ordering::Less),
+            ( true, true ) => None,
+        }
+    }
+}
+impl Meet for Union<BitSet> {
+    fn meet_in(val: &mut BitSet, other: BitSet) {
+        val.words.truncate(other.words.len());
+        for ( a, b ) in val.words.iter_mut().zip(other.words) {
+            *a &= b;
+        }
+        val.trim();
+    }
+}
+impl MergeRef for Union<BitSet> {
+    fn merge_in_ref(val: &mut BitSet, other: &BitSet) {
+        if val.words.len() < other.words.len() {
+            val.words.resize(other.words.len(), 0);
+        }
+        for ( a, b ) in val.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+}
+impl Bottom for Union<BitSet> {
+    fn bottom() -> BitSet {
+        BitSet::new()
+    }
+}
+
+
+
+// MAP MERGES //
+
+/// Map join: keeps every key, merging the values of keys in both maps with
+/// the value merge. `T` is a map from keys to merges, e.g.
+/// `MapUnion<HashMap<K, Max<u64>>>` has domain `HashMap<K, u64>`.
+pub struct MapUnion<T> {
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl <M: MapLike> Merge for MapUnion<M>
+where
+    M::Val: Merge,
+{
+    type Domain = M::Rebind<<M::Val as Merge>::Domain>;
+
+    fn merge_in(val: &mut Self::Domain, mut other: Self::Domain) {
+        // Insert the smaller map into the larger.
+        if val.len() < other.len() {
+            std::mem::swap(val, &mut other);
+        }
+        for (k, v) in other {
+            val.insert_or_merge(k, v, M::Val::merge_in);
+        }
+    }
+
+    fn merge_all(val: &mut Self::Domain, others: impl IntoIterator<Item = Self::Domain>) {
+        val.extend_all(others.into_iter().collect(), M::Val::merge_in);
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        // Missing keys are bottom, so only the map with more keys can be
+        // greater. Compare the values of the smaller map's keys.
+        let mut result = val.len().cmp(&other.len());
+        if Ordering::Greater == result {
+            for (k, other_val) in other.iter() {
+                result = product_cmp(result, M::Val::partial_cmp(val.get(k)?, other_val)?)?;
+            }
+        }
+        else {
+            for (k, val_val) in val.iter() {
+                result = product_cmp(result, M::Val::partial_cmp(val_val, other.get(k)?)?)?;
+            }
+        }
+        Some(result)
+    }
+}
+impl <M: MapLike> Meet for MapUnion<M>
+where
+    M::Val: Meet,
+{
+    fn meet_in(val: &mut Self::Domain, mut other: Self::Domain) {
+        // Filter the smaller map by the larger.
+        if val.len() > other.len() {
+            std::mem::swap(val, &mut other);
+        }
+        val.retain(|k, v0| {
+            match other.remove(k) {
+                Some(v) => {
+                    M::Val::meet_in(v0, v);
+                    true
+                },
+                None => false,
+            }
+        });
+    }
+}
+impl <M: MapLike> Widen for MapUnion<M>
+where
+    M::Val: Widen,
+{
+    fn widen_in(val: &mut Self::Domain, other: Self::Domain) {
+        for (k, v) in other {
+            val.insert_or_merge(k, v, M::Val::widen_in);
+        }
+    }
+
+    fn narrow_in(val: &mut Self::Domain, mut other: Self::Domain) {
+        val.retain(|k, v0| {
+            match other.remove(k) {
+                Some(v) => {
+                    M::Val::narrow_in(v0, v);
+                    true
+                },
+                None => false,
+            }
+        });
+    }
+}
+impl <M: MapLike> DeltaMerge for MapUnion<M>
+where
+    M::Key: Clone,
+    M::Val: DeltaMerge,
+    <M::Val as Merge>::Domain: Clone,
+{
+    fn merge_in_delta(val: &mut Self::Domain, other: Self::Domain) -> Option<Self::Domain> {
+        let mut delta = Self::Domain::default();
+        for (k, v) in other {
+            match val.get_mut(&k) {
+                Some(val_val) => {
+                    if let Some(v_delta) = M::Val::merge_in_delta(val_val, v) {
+                        delta.insert(k, v_delta);
+                    }
+                },
+                None => {
+                    delta.insert(k.clone(), v.clone());
+                    val.insert(k, v);
+                },
+            }
+        }
+        if delta.is_empty() {
+            None
+        }
+        else {
+            Some(delta)
+        }
+    }
+}
+impl <M: MapLike> MergeRef for MapUnion<M>
+where
+    M::Key: Clone,
+    M::Val: MergeRef,
+    <M::Val as Merge>::Domain: Clone,
+{
+    fn merge_in_ref(val: &mut Self::Domain, other: &Self::Domain) {
+        for (k, v) in other.iter() {
+            match val.get_mut(k) {
+                Some(val_val) => M::Val::merge_in_ref(val_val, v),
+                None => {
+                    val.insert(k.clone(), v.clone());
+                },
+            }
+        }
+    }
+}
+impl <M: MapLike> Bottom for MapUnion<M>
+where
+    M::Val: Merge,
+{
+    fn bottom() -> Self::Domain {
+        Self::Domain::default()
+    }
+}
+
+/// Map meet: keeps only the keys present in both maps, merging their values
+/// with the value merge. Dropping keys moves up the order, so a map with
+/// fewer keys (and greater values) is greater.
+pub struct MapIntersection<T> {
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl <M: MapLike> Merge for MapIntersection<M>
+where
+    M::Val: Merge,
+{
+    type Domain = M::Rebind<<M::Val as Merge>::Domain>;
+
+    fn merge_in(val: &mut Self::Domain, mut other: Self::Domain) {
+        // Filter the smaller map by the larger.
+        if val.len() > other.len() {
+            std::mem::swap(val, &mut other);
+        }
+        val.retain(|k, v0| {
+            match other.remove(k) {
+                Some(v) => {
+                    M::Val::merge_in(v0, v);
+                    true
+                },
+                None => false,
+            }
+        });
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        // Dropped keys are top, so only the map with fewer keys can be
+        // greater. Compare the values of the smaller map's keys.
+        let mut result = other.len().cmp(&val.len());
+        if Ordering::Less == result {
+            for (k, other_val) in other.iter() {
+                result = product_cmp(result, M::Val::partial_cmp(val.get(k)?, other_val)?)?;
+            }
+        }
+        else {
+            for (k, val_val) in val.iter() {
+                result = product_cmp(result, M::Val::partial_cmp(val_val, other.get(k)?)?)?;
+            }
+        }
+        Some(result)
+    }
+}
+impl <M: MapLike> Meet for MapIntersection<M>
+where
+    M::Val: Meet,
+{
+    fn meet_in(val: &mut Self::Domain, mut other: Self::Domain) {
+        // Insert the smaller map into the larger.
+        if val.len() < other.len() {
+            std::mem::swap(val, &mut other);
+        }
+        for (k, v) in other {
+            val.insert_or_merge(k, v, M::Val::meet_in);
+        }
+    }
+}
+impl <M: MapLike> Top for MapIntersection<M>
+where
+    M::Val: Merge,
+{
+    fn top() -> Self::Domain {
+        Self::Domain::default()
+    }
+}
+
+/// Lexicographic pair where the second component is merged only while the
+/// first components are equal.
+///
+/// This is only a lattice if `AF` is a chain (totally ordered, like `Max`),
+/// otherwise `merge_in` is not associative. Use `Lexicographic` for any `AF`.
+pub struct DominatingPair<AF, BF>
+where
+    AF: Merge,
+    BF: Merge,
+{
+    _phantom: std::marker::PhantomData<(AF, BF)>,
+}
+
+impl <AF, BF> Merge for DominatingPair<AF, BF>
+where
+    AF: Merge,
+    BF: Merge,
+{
+    type Domain = (<AF as Merge>::Domain, <BF as Merge>::Domain);
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        let cmp = AF::partial_cmp(&val.0, &other.0);
+        match cmp {
+            None => {
+                AF::merge_in(&mut val.0, other.0);
+                BF::merge_in(&mut val.1, other.1);
+            },
+            Some(Ordering::Equal) => {
+                BF::merge_in(&mut val.1, other.1);
+            },
+            Some(Ordering::Less) => {
+                *val = other;
+            },
+            Some(Ordering::Greater) => {},
+        }
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        match AF::partial_cmp(&val.0, &other.0) {
+            Some(Ordering::Equal) => BF::partial_cmp(&val.1, &other.1),
+            cmp => cmp,
+        }
+    }
+}
+impl <AF, BF> DeltaMerge for DominatingPair<AF, BF>
+where
+    AF: DeltaMerge,
+    BF: DeltaMerge,
+    AF::Domain: Clone,
+    BF::Domain: Clone,
+{
+    fn merge_in_delta(val: &mut Self::Domain, other: Self::Domain) -> Option<Self::Domain> {
+        let cmp = AF::partial_cmp(&val.0, &other.0);
+        match cmp {
+            // Both sides change, so the delta is the whole new value.
+            None => {
+                AF::merge_in(&mut val.0, other.0);
+                BF::merge_in(&mut val.1, other.1);
+                Some(val.clone())
+            },
+            Some(Ordering::Equal) => {
+                BF::merge_in_delta(&mut val.1, other.1)
+                    .map(|delta| ( val.0.clone(), delta ))
+            },
+            Some(Ordering::Less) => {
+                *val = other;
+                Some(val.clone())
+            },
+            Some(Ordering::Greater) => None,
+        }
+    }
+}
+impl <AF, BF> Bottom for DominatingPair<AF, BF>
+where
+    AF: Bottom,
+    BF: Bottom,
+{
+    fn bottom() -> Self::Domain {
+        ( AF::bottom(), BF::bottom() )
+    }
+}
+impl <AF, BF> Top for DominatingPair<AF, BF>
+where
+    AF: Top,
+    BF: Top,
+{
+    fn top() -> Self::Domain {
+        ( AF::top(), BF::top() )
+    }
+}
+
+/// Lexicographic product, ordered by the first component and then by the
+/// second. A lattice for any `AF`: when the first components are
+/// incomparable their merge dominates both, so the second component resets
+/// to `BF`'s bottom.
+pub struct Lexicographic<AF, BF>
+where
+    AF: Merge,
+    BF: Bottom,
+{
+    _phantom: std::marker::PhantomData<(AF, BF)>,
+}
+
+impl <AF, BF> Merge for Lexicographic<AF, BF>
+where
+    AF: Merge,
+    BF: Bottom,
+{
+    type Domain = (<AF as Merge>::Domain, <BF as Merge>::Domain);
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        let cmp = AF::partial_cmp(&val.0, &other.0);
+        match cmp {
+            None => {
+                AF::merge_in(&mut val.0, other.0);
+                val.1 = BF::bottom();
+            },
+            Some(Ordering::Equal) => {
+                BF::merge_in(&mut val.1, other.1);
+            },
+            Some(Ordering::Less) => {
+                *val = other;
+            },
+            Some(Ordering::Greater) => {},
+        }
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        match AF::partial_cmp(&val.0, &other.0) {
+            Some(Ordering::Equal) => BF::partial_cmp(&val.1, &other.1),
+            cmp => cmp,
+        }
+    }
+}
+impl <AF, BF> Bottom for Lexicographic<AF, BF>
+where
+    AF: Bottom,
+    BF: Bottom,
+{
+    fn bottom() -> Self::Domain {
+        ( AF::bottom(), BF::bottom() )
+    }
+}
+impl <AF, BF> Top for Lexicographic<AF, BF>
+where
+    AF: Top,
+    BF: Bottom + Top,
+{
+    fn top() -> Self::Domain {
+        ( AF::top(), BF::top() )
+    }
+}
+
+// PRODUCT MERGES //
+
+/// Combines the orderings of two components of a product lattice.
+/// Components must agree (or be equal) for the product to be ordered.
+fn product_cmp(a: Ordering, b: Ordering) -> Option<Ordering> {
+    match (a, b) {
+        (Ordering::Equal, cmp) | (cmp, Ordering::Equal) => Some(cmp),
+        (a, b) if a == b => Some(a),
+        _ => None,
+    }
+}
+
+/// Pointwise product of two lattices. Unlike `DominatingPair`, both
+/// components are always merged, and the pair is only ordered when both
+/// components are ordered the same way.
+pub struct Pair<AF, BF>
+where
+    AF: Merge,
+    BF: Merge,
+{
+    _phantom: std::marker::PhantomData<(AF, BF)>,
+}
+
+impl <AF, BF> Merge for Pair<AF, BF>
+where
+    AF: Merge,
+    BF: Merge,
+{
+    type Domain = (<AF as Merge>::Domain, <BF as Merge>::Domain);
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        <(AF, BF) as Merge>::merge_in(val, other);
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        <(AF, BF) as Merge>::partial_cmp(val, other)
+    }
+}
+impl <AF, BF> Meet for Pair<AF, BF>
+where
+    AF: Meet,
+    BF: Meet,
+{
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+        <(AF, BF) as Meet>::meet_in(val, other);
+    }
+}
+impl <AF, BF> Bottom for Pair<AF, BF>
+where
+    AF: Bottom,
+    BF: Bottom,
+{
+    fn bottom() -> Self::Domain {
+        ( AF::bottom(), BF::bottom() )
+    }
+}
+impl <AF, BF> Top for Pair<AF, BF>
+where
+    AF: Top,
+    BF: Top,
+{
+    fn top() -> Self::Domain {
+        ( AF::top(), BF::top() )
+    }
+}
+
+// Tuples of merges are pointwise products, e.g. `(Max<u64>, Union<HashSet<T>>)`.
+macro_rules! tuple_merge {
+    ( $( $f:ident $i:tt ),+ ) => {
+        impl <$( $f: Merge ),+> Merge for ( $( $f, )+ ) {
+            type Domain = ( $( <$f as Merge>::Domain, )+ );
+
+            fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+                $( $f::merge_in(&mut val.$i, other.$i); )+
+            }
+
+            fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+                let result = Ordering::Equal;
+                $( let result = product_cmp(result, $f::partial_cmp(&val.$i, &other.$i)?)?; )+
+                Some(result)
+            }
+        }
+        impl <$( $f: Meet ),+> Meet for ( $( $f, )+ ) {
+            fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+                $( $f::meet_in(&mut val.$i, other.$i); )+
+            }
+        }
+        impl <$( $f: Bottom ),+> Bottom for ( $( $f, )+ ) {
+            fn bottom() -> Self::Domain {
+                ( $( $f::bottom(), )+ )
+            }
+        }
+        impl <$( $f: Top ),+> Top for ( $( $f, )+ ) {
+            fn top() -> Self::Domain {
+                ( $( $f::top(), )+ )
+            }
+        }
+    };
+}
+tuple_merge!(A 0);
+tuple_merge!(A 0, B 1);
+tuple_merge!(A 0, B 1, C 2);
+tuple_merge!(A 0, B 1, C 2, D 3);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+tuple_merge!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+// POINTWISE MERGES //
+
+/// Merges sequences index by index. `Pointwise<Vec<F>>` grows to the longer
+/// length, like a `MapUnion` over dense indices; `Pointwise<[F; N]>` has a
+/// fixed length.
+pub struct Pointwise<T> {
+    _phantom: std::marker::PhantomData<T>,
+}
+impl <F: Merge> Merge for Pointwise<Vec<F>> {
+    type Domain = Vec<F::Domain>;
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        for (i, v) in other.into_iter().enumerate() {
+            if i < val.len() {
+                F::merge_in(&mut val[i], v);
+            }
+            else {
+                val.push(v);
+            }
+        }
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        // Extra indices are greater.
+        let mut result = val.len().cmp(&other.len());
+        for (val_val, other_val) in val.iter().zip(other) {
+            result = product_cmp(result, F::partial_cmp(val_val, other_val)?)?;
+        }
+        Some(result)
+    }
+}
+impl <F: Meet> Meet for Pointwise<Vec<F>> {
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+        val.truncate(other.len());
+        for (val_val, v) in val.iter_mut().zip(other) {
+            F::meet_in(val_val, v);
+        }
+    }
+}
+impl <F: Merge> Bottom for Pointwise<Vec<F>> {
+    fn bottom() -> Self::Domain {
+        Vec::new()
+    }
+}
+
+impl <F: Merge, const N: usize> Merge for Pointwise<[F; N]> {
+    type Domain = [F::Domain; N];
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        for (val_val, v) in val.iter_mut().zip(IntoIterator::into_iter(other)) {
+            F::merge_in(val_val, v);
+        }
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        let mut result = Ordering::Equal;
+        for (val_val, other_val) in val.iter().zip(other) {
+            result = product_cmp(result, F::partial_cmp(val_val, other_val)?)?;
+        }
+        Some(result)
+    }
+}
+impl <F: Meet, const N: usize> Meet for Pointwise<[F; N]> {
+    fn meet_in(val: &mut Self::Domain, other: Self::Domain) {
+        for (val_val, v) in val.iter_mut().zip(IntoIterator::into_iter(other)) {
+            F::meet_in(val_val, v);
+        }
+    }
+}
+impl <F: Bottom, const N: usize> Bottom for Pointwise<[F; N]> {
+    fn bottom() -> Self::Domain {
+        [(); N].map(|_| F::bottom())
+    }
+}
+impl <F: Top, const N: usize> Top for Pointwise<[F; N]> {
+    fn top() -> Self::Domain {
+        [(); N].map(|_| F::top())
+    }
+}
+
+// CRDT MERGES //
+
+/// Causal relationship between two vector clocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Causality {
+    Equal,
+    HappensBefore,
+    HappensAfter,
+    Concurrent,
+}
+impl From<Option<Ordering>> for Causality {
+    fn from(cmp: Option<Ordering>) -> Self {
+        match cmp {
+            Some(Ordering::Equal) => Causality::Equal,
+            Some(Ordering::Less) => Causality::HappensBefore,
+            Some(Ordering::Greater) => Causality::HappensAfter,
+            None => Causality::Concurrent,
+        }
+    }
+}
+
+/// Vector clock, a `MapUnion<HashMap<K, Max<u64>>>` of per-node event counts.
+/// Is its own merge, so use as `VClock<K>` in either position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VClock<K: Hash + Eq> {
+    // Never holds zero counts, so a missing node and a zero count are the
+    // same clock to both `==` and `partial_cmp`.
+    clock: HashMap<K, u64>,
+}
+impl <K: Hash + Eq> VClock<K> {
+    pub fn new() -> Self {
+        Self {
+            clock: HashMap::new(),
+        }
+    }
+
+    /// Records a new event at `node`, returning its new count.
+    pub fn increment(&mut self, node: K) -> u64 {
+        let count = self.clock.entry(node).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    /// Number of events seen from `node`.
+    pub fn get(&self, node: &K) -> u64 {
+        self.clock.get(node).cloned().unwrap_or(0)
+    }
+
+    pub fn causality(&self, other: &Self) -> Causality {
+        Self::partial_cmp(self, other).into()
+    }
+
+    /// If `self` has seen every event `other` has (including if equal).
+    pub fn dominates(&self, other: &Self) -> bool {
+        matches!(self.causality(other), Causality::Equal | Causality::HappensAfter)
+    }
+
+    pub fn happens_before(&self, other: &Self) -> bool {
+        Causality::HappensBefore == self.causality(other)
+    }
+
+    pub fn concurrent_with(&self, other: &Self) -> bool {
+        Causality::Concurrent == self.causality(other)
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, K, u64> {
+        self.clock.iter()
+    }
+}
+impl <K: Hash + Eq> Default for VClock<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <K: Hash + Eq> From<HashMap<K, u64>> for VClock<K> {
+    /// Zero counts are dropped, as they are the same as no events.
+    fn from(mut clock: HashMap<K, u64>) -> Self {
+        clock.retain(|_, &mut count| 0 < count);
+        Self {
+            clock: clock,
+        }
+    }
+}
+impl <K: Hash + Eq> Merge for VClock<K> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        <MapUnion<HashMap<K, Max<u64>>> as Merge>::merge_in(&mut val.clock, other.clock);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        <MapUnion<HashMap<K, Max<u64>>> as Merge>::partial_cmp(&val.clock, &other.clock)
+    }
+}
+impl <K: Hash + Eq> Bottom for VClock<K> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Last-writer-wins register. The write with the greatest timestamp wins,
+/// and the value is never merged.
+///
+/// Writes with equal timestamps are broken by the greater value, so replicas
+/// agree however the writes arrive. To have a specific writer win ties
+/// instead, include the replica's id in `Ts`, e.g. `(u64, ReplicaId)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LwwRegister<T, Ts: Ord> {
+    ts: Ts,
+    value: T,
+}
+impl <T, Ts: Ord> LwwRegister<T, Ts> {
+    pub fn new(ts: Ts, value: T) -> Self {
+        Self {
+            ts: ts,
+            value: value,
+        }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn timestamp(&self) -> &Ts {
+        &self.ts
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+impl <T: Ord, Ts: Ord> LwwRegister<T, Ts> {
+    /// Writes `value` at time `ts`. Has no effect if `ts` is older than the
+    /// current write.
+    pub fn set(&mut self, ts: Ts, value: T) {
+        Self::merge_in(self, Self::new(ts, value));
+    }
+}
+impl <T: Ord, Ts: Ord> Merge for LwwRegister<T, Ts> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        if Some(Ordering::Less) == Self::partial_cmp(val, &other) {
+            *val = other;
+        }
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        Some(val.ts.cmp(&other.ts).then_with(|| val.value.cmp(&other.value)))
+    }
+}
+
+/// Multi-value register. Keeps every causally maximal write, tagged with its
+/// `VClock`, so concurrent writes show up as siblings instead of one being
+/// silently dropped.
+#[derive(Clone, Debug)]
+pub struct MvRegister<T, K: Hash + Eq> {
+    siblings: Vec<(VClock<K>, T)>,
+}
+impl <T, K: Hash + Eq> MvRegister<T, K> {
+    pub fn new() -> Self {
+        Self {
+            siblings: Vec::new(),
+        }
+    }
+
+    /// Writes `value` from `node`, superseding all currently observed siblings.
+    pub fn write(&mut self, node: K, value: T) {
+        let mut clock = VClock::new();
+        for ( sibling_clock, _ ) in self.siblings.drain(..) {
+            VClock::merge_in(&mut clock, sibling_clock);
+        }
+        clock.increment(node);
+        self.siblings.push(( clock, value ));
+    }
+
+    /// The concurrent values currently held, empty if never written.
+    pub fn siblings(&self) -> impl Iterator<Item = &T> {
+        self.siblings.iter().map(|( _, value )| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.siblings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.siblings.is_empty()
+    }
+
+}
+impl <T, K: Hash + Eq> Default for MvRegister<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+// Siblings are unordered.
+impl <T: PartialEq, K: Hash + Eq> PartialEq for MvRegister<T, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.siblings.len() == other.siblings.len()
+            && self.siblings.iter().all(|sibling| other.siblings.contains(sibling))
+    }
+}
+impl <T: Eq, K: Hash + Eq> Eq for MvRegister<T, K> {}
+impl <T: Ord, K: Hash + Eq> MvRegister<T, K> {
+    /// If every sibling of `val` is covered by some sibling of `other`: one
+    /// with a later clock, or the same clock and a greater or equal value.
+    fn covered_by(val: &Self, other: &Self) -> bool {
+        val.siblings.iter()
+            .all(|( clock, value )| other.siblings.iter().any(|( other_clock, other_value )| {
+                match other_clock.causality(clock) {
+                    Causality::HappensAfter => true,
+                    Causality::Equal => value <= other_value,
+                    _ => false,
+                }
+            }))
+    }
+}
+impl <T: Ord, K: Hash + Eq> Merge for MvRegister<T, K> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        let mut siblings = std::mem::take(&mut val.siblings);
+        siblings.extend(other.siblings);
+
+        // Keep only causally maximal siblings. Of siblings with equal clocks
+        // keep the greatest value (the first, if duplicated), so the result
+        // doesn't depend on argument order.
+        let keep: Vec<bool> = (0..siblings.len())
+            .map(|i| !siblings.iter().enumerate().any(|( j, ( clock, value ) )| {
+                match clock.causality(&siblings[i].0) {
+                    Causality::HappensAfter => true,
+                    Causality::Equal => match value.cmp(&siblings[i].1) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => j < i,
+                        Ordering::Less => false,
+                    },
+                    _ => false,
+                }
+            }))
+            .collect();
+        val.siblings = siblings.into_iter()
+            .zip(keep)
+            .filter(|( _, keep )| *keep)
+            .map(|( sibling, _ )| sibling)
+            .collect();
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        match ( Self::covered_by(val, other), Self::covered_by(other, val) ) {
+            ( true, true ) => Some(Ordering::Equal),
+            ( true, false ) => Some(Ordering::Less),
+            ( false, true ) => Some(Ordering::Greater),
+            ( false, false ) => None,
+        }
+    }
+}
+impl <T: Ord, K: Hash + Eq> Bottom for MvRegister<T, K> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Merge for an `OrSet`'s elements: each element maps to its add tags and its
+/// removed (tombstoned) tags.
+type OrSetTags<T, K> = MapUnion<HashMap<T, Pair<Union<HashSet<(K, u64)>>, Union<HashSet<(K, u64)>>>>>;
+
+/// Observed-remove set. Every insert is tagged uniquely by `(node, count)`,
+/// and a remove only tombstones the tags it has observed, so a concurrent
+/// insert of the same element survives the remove.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrSet<T: Hash + Eq, K: Hash + Eq> {
+    tags: <OrSetTags<T, K> as Merge>::Domain,
+    clock: VClock<K>,
+}
+impl <T: Hash + Eq, K: Hash + Eq + Clone> OrSet<T, K> {
+    pub fn new() -> Self {
+        Self {
+            tags: HashMap::new(),
+            clock: VClock::new(),
+        }
+    }
+
+    /// Inserts `value` from `node`.
+    pub fn insert(&mut self, node: K, value: T) {
+        let count = self.clock.increment(node.clone());
+        let ( adds, _ ) = self.tags.entry(value).or_default();
+        adds.insert(( node, count ));
+    }
+
+    /// Removes `value`, returning if it was present.
+    pub fn remove(&mut self, value: &T) -> bool {
+        match self.tags.get_mut(value) {
+            Some(( adds, removes )) => {
+                let present = !removes.is_superset(adds);
+                removes.extend(adds.iter().cloned());
+                present
+            },
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        match self.tags.get(value) {
+            Some(( adds, removes )) => !removes.is_superset(adds),
+            None => false,
+        }
+    }
+
+    /// Iterates the elements currently present.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tags.iter()
+            .filter(|( _, ( adds, removes ) )| !removes.is_superset(adds))
+            .map(|( value, _ )| value)
+    }
+}
+impl <T: Hash + Eq, K: Hash + Eq + Clone> Default for OrSet<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <T: Hash + Eq, K: Hash + Eq> Merge for OrSet<T, K> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        OrSetTags::<T, K>::merge_in(&mut val.tags, other.tags);
+        VClock::merge_in(&mut val.clock, other.clock);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        product_cmp(
+            OrSetTags::<T, K>::partial_cmp(&val.tags, &other.tags)?,
+            VClock::partial_cmp(&val.clock, &other.clock)?,
+        )
+    }
+}
+impl <T: Hash + Eq, K: Hash + Eq + Clone> Bottom for OrSet<T, K> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Grow-only counter, a `MapUnion<HashMap<K, Max<u64>>>` of per-replica counts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GCounter<K: Hash + Eq> {
+    counts: HashMap<K, u64>,
+}
+impl <K: Hash + Eq> GCounter<K> {
+    pub fn new() -> Self {
+        Self {
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Adds `n` to `replica`'s count. A zero `n` is a no-op, so no zero
+    /// entries are stored (they would compare equal but not `==`).
+    pub fn increment(&mut self, replica: K, n: u64) {
+        if 0 < n {
+            *self.counts.entry(replica).or_insert(0) += n;
+        }
+    }
+
+    /// The total count across all replicas.
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// The count contributed by `replica`.
+    pub fn get(&self, replica: &K) -> u64 {
+        self.counts.get(replica).cloned().unwrap_or(0)
+    }
+}
+impl <K: Hash + Eq> Default for GCounter<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <K: Hash + Eq> Merge for GCounter<K> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        <MapUnion<HashMap<K, Max<u64>>> as Merge>::merge_in(&mut val.counts, other.counts);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        <MapUnion<HashMap<K, Max<u64>>> as Merge>::partial_cmp(&val.counts, &other.counts)
+    }
+}
+impl <K: Hash + Eq> Bottom for GCounter<K> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Positive-negative counter, a pair of `GCounter`s for increments and decrements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PnCounter<K: Hash + Eq> {
+    inc: GCounter<K>,
+    dec: GCounter<K>,
+}
+impl <K: Hash + Eq> PnCounter<K> {
+    pub fn new() -> Self {
+        Self {
+            inc: GCounter::new(),
+            dec: GCounter::new(),
+        }
+    }
+
+    pub fn increment(&mut self, replica: K, n: u64) {
+        self.inc.increment(replica, n);
+    }
+
+    pub fn decrement(&mut self, replica: K, n: u64) {
+        self.dec.increment(replica, n);
+    }
+
+    /// Total increments minus total decrements. Summed in `i128`, which is
+    /// exact for any realistic number of replicas.
+    pub fn value(&self) -> i128 {
+        let total = |counter: &GCounter<K>| counter.counts.values().map(|&n| n as i128).sum::<i128>();
+        total(&self.inc) - total(&self.dec)
+    }
+}
+impl <K: Hash + Eq> Default for PnCounter<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <K: Hash + Eq> Merge for PnCounter<K> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        GCounter::merge_in(&mut val.inc, other.inc);
+        GCounter::merge_in(&mut val.dec, other.dec);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        product_cmp(
+            GCounter::partial_cmp(&val.inc, &other.inc)?,
+            GCounter::partial_cmp(&val.dec, &other.dec)?,
+        )
+    }
+}
+impl <K: Hash + Eq> Bottom for PnCounter<K> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Two-phase set, a pair of `Union`s of added and removed elements. Once
+/// removed, an element can never be re-added.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TwoPhaseSet<T: Hash + Eq> {
+    adds: HashSet<T>,
+    removes: HashSet<T>,
+}
+impl <T: Hash + Eq> TwoPhaseSet<T> {
+    pub fn new() -> Self {
+        Self {
+            adds: HashSet::new(),
+            removes: HashSet::new(),
+        }
+    }
+
+    /// Inserts `value`, returning `false` if it was already removed.
+    pub fn insert(&mut self, value: T) -> bool {
+        let removed = self.removes.contains(&value);
+        self.adds.insert(value);
+        !removed
+    }
+
+    /// Removes `value` permanently.
+    pub fn remove(&mut self, value: T) {
+        self.removes.insert(value);
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.adds.contains(value) && !self.removes.contains(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.adds.difference(&self.removes)
+    }
+}
+impl <T: Hash + Eq> Default for TwoPhaseSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <T: Hash + Eq> Merge for TwoPhaseSet<T> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        Union::<HashSet<T>>::merge_in(&mut val.adds, other.adds);
+        Union::<HashSet<T>>::merge_in(&mut val.removes, other.removes);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        product_cmp(
+            Union::<HashSet<T>>::partial_cmp(&val.adds, &other.adds)?,
+            Union::<HashSet<T>>::partial_cmp(&val.removes, &other.removes)?,
+        )
+    }
+}
+impl <T: Hash + Eq> Bottom for TwoPhaseSet<T> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Which operation wins when an element's add and remove timestamps are
+/// equal, for `LwwElementSet`.
+pub trait Bias {
+    fn add_wins() -> bool;
+}
+
+/// Ties keep the element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AddWins;
+impl Bias for AddWins {
+    fn add_wins() -> bool {
+        true
+    }
+}
+
+/// Ties remove the element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RemoveWins;
+impl Bias for RemoveWins {
+    fn add_wins() -> bool {
+        false
+    }
+}
+
+/// Merge for an `LwwElementSet`'s elements: each element maps to its latest
+/// add and latest remove timestamps.
+type LwwElementTimestamps<T, Ts> = MapUnion<HashMap<T, Pair<Max<Option<Ts>>, Max<Option<Ts>>>>>;
+
+/// Last-writer-wins element set. Each element keeps its latest add and remove
+/// timestamps, and is present if its add is newer than its remove. Ties are
+/// settled by the `Bias` `B`, part of the type so replicas can't disagree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LwwElementSet<T: Hash + Eq, Ts: Ord, B: Bias> {
+    timestamps: <LwwElementTimestamps<T, Ts> as Merge>::Domain,
+    _phantom: std::marker::PhantomData<B>,
+}
+impl <T: Hash + Eq, Ts: Ord, B: Bias> LwwElementSet<T, Ts, B> {
+    pub fn new() -> Self {
+        Self {
+            timestamps: HashMap::new(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub fn insert(&mut self, value: T, ts: Ts) {
+        let ( add_ts, _ ) = self.timestamps.entry(value).or_insert(( None, None ));
+        Max::merge_in(add_ts, Some(ts));
+    }
+
+    pub fn remove(&mut self, value: T, ts: Ts) {
+        let ( _, remove_ts ) = self.timestamps.entry(value).or_insert(( None, None ));
+        Max::merge_in(remove_ts, Some(ts));
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        match self.timestamps.get(value) {
+            Some(( add_ts, remove_ts )) => Self::is_present(add_ts, remove_ts),
+            None => false,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.timestamps.iter()
+            .filter(|( _, ( add_ts, remove_ts ) )| Self::is_present(add_ts, remove_ts))
+            .map(|( value, _ )| value)
+    }
+
+    fn is_present(add_ts: &Option<Ts>, remove_ts: &Option<Ts>) -> bool {
+        match add_ts.cmp(remove_ts) {
+            Ordering::Greater => true,
+            Ordering::Equal => add_ts.is_some() && B::add_wins(),
+            Ordering::Less => false,
+        }
+    }
+}
+impl <T: Hash + Eq, Ts: Ord, B: Bias> Default for LwwElementSet<T, Ts, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <T: Hash + Eq, Ts: Ord, B: Bias> Merge for LwwElementSet<T, Ts, B> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        LwwElementTimestamps::<T, Ts>::merge_in(&mut val.timestamps, other.timestamps);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        LwwElementTimestamps::<T, Ts>::partial_cmp(&val.timestamps, &other.timestamps)
+    }
+}
+impl <T: Hash + Eq, Ts: Ord, B: Bias> Bottom for LwwElementSet<T, Ts, B> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+
+// SKETCH MERGES //
+// Fixed-size probabilistic summaries, for when the exact set would grow
+// without bound. Replicas must hash items identically, so items are hashed
+// with `SketchHasher`, which is fully specified here rather than left to the
+// standard library.
+
+/// 64-bit FNV-1a followed by the MurmurHash3 `fmix64` finalizer, which FNV
+/// needs to spread short inputs over the high bits. Integers are hashed as
+/// little-endian bytes and `usize` as a `u64`, so the hash is the same on
+/// every platform, as long as the item's `Hash` impl is.
+struct SketchHasher(u64);
+impl SketchHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+impl Hasher for SketchHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        let mut h = self.0;
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^= h >> 33;
+        h
+    }
+}
+
+fn sketch_hash<T: Hash + ?Sized>(item: &T) -> u64 {
+    let mut hasher = SketchHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// HyperLogLog sketch for approximately counting distinct items, with `2^P`
+/// registers (`P` from 4 to 16). The relative error is about
+/// `1.04 / sqrt(2^P)`, e.g. 1.6% for `P = 12`, using `2^P` bytes.
+/// Is its own merge (pointwise max of registers), so use as
+/// `HyperLogLog<P>` in either position, e.g. as the value merge in a
+/// `MapUnion` for per-key counts.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HyperLogLog<const P: u8> {
+    registers: Vec<u8>,
+}
+impl <const P: u8> HyperLogLog<P> {
+    pub fn new() -> Self {
+        // Fails the build, rather than panicking, for invalid precisions.
+        const { assert!(4 <= P && P <= 16, "HyperLogLog precision must be from 4 to 16.") };
+        Self {
+            registers: vec![ 0; 1 << P ],
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let hash = sketch_hash(item);
+        // First `P` bits pick the register, the rest give the rank: the
+        // position of the first one bit, capped by a sentinel bit.
+        let index = (hash >> (64 - P)) as usize;
+        let rank = ((hash << P) | (1 << (P - 1))).leading_zeros() as u8 + 1;
+        Max::merge_in(&mut self.registers[index], rank);
+    }
+
+    /// Estimated number of distinct items inserted.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match P {
+            4 => 0.673,
+            5 => 0.697,
+            6 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter()
+            .map(|&rank| (-(rank as f64)).exp2())
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        // Linear counting is more accurate for small counts.
+        let zeros = self.registers.iter().filter(|&&rank| 0 == rank).count();
+        if raw <= 2.5 * m && 0 < zeros {
+            m * (m / zeros as f64).ln()
+        }
+        else {
+            raw
+        }
+    }
+
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+}
+impl <const P: u8> Default for HyperLogLog<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <const P: u8> Merge for HyperLogLog<P> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        Self::merge_in_ref(val, &other);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        Pointwise::<Vec<Max<u8>>>::partial_cmp(&val.registers, &other.registers)
+    }
+}
+impl <const P: u8> MergeRef for HyperLogLog<P> {
+    fn merge_in_ref(val: &mut Self, other: &Self) {
+        for ( rank, &other_rank ) in val.registers.iter_mut().zip(&other.registers) {
+            Max::merge_in(rank, other_rank);
+        }
+    }
+}
+impl <const P: u8> Bottom for HyperLogLog<P> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+/// Sizes a `BloomFilter` to hold `expected_items` at the given
+/// false-positive rate, returning `( BITS, K )`.
+pub fn bloom_filter_params(expected_items: usize, false_positive_rate: f64) -> ( usize, u32 ) {
+    assert!(0.0 < false_positive_rate && false_positive_rate < 1.0,
+        "BloomFilter false-positive rate must be between 0 and 1 exclusive, got {}.", false_positive_rate);
+    let n = expected_items.max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+    let num_bits = (-n * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(1.0);
+    let num_hashes = (num_bits / n * ln2).round().max(1.0);
+    ( num_bits as usize, num_hashes as u32 )
+}
+
+/// Bloom filter for approximate membership over `BITS` bits with `K` hashes:
+/// no false negatives, and false positives at a rate set by `BITS` and `K`
+/// (see `bloom_filter_params`). Is its own merge (bitwise or), so use as
+/// `BloomFilter<BITS, K>` in either position.
+///
+/// Only filters with the same parameters merge, which the type enforces. Use
+/// `try_merge_in` to merge filters whose parameters are only known to match
+/// at runtime.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloomFilter<const BITS: usize, const K: u32> {
+    bits: BitSet,
+}
+impl <const BITS: usize, const K: u32> BloomFilter<BITS, K> {
+    pub fn new() -> Self {
+        // Fails the build, rather than panicking, for invalid parameters.
+        const { assert!(0 < BITS && 0 < K, "BloomFilter needs at least one bit and one hash.") };
+        Self {
+            bits: BitSet::new(),
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        for i in Self::indices(item) {
+            self.bits.insert(i);
+        }
+    }
+
+    /// False if `item` was definitely never inserted.
+    pub fn may_contain<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        Self::indices(item).all(|i| self.bits.contains(i))
+    }
+
+    /// Estimated chance that `may_contain` is true for an item never
+    /// inserted, given the bits set so far.
+    pub fn false_positive_rate(&self) -> f64 {
+        let fill = self.bits.len() as f64 / BITS as f64;
+        fill.powi(K as i32)
+    }
+
+    pub fn num_bits(&self) -> usize {
+        BITS
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        K
+    }
+
+    /// Merges `other` into `val`, or returns an error (leaving `val`
+    /// unchanged) if their parameters differ.
+    pub fn try_merge_in<const OTHER_BITS: usize, const OTHER_K: u32>(val: &mut Self, other: BloomFilter<OTHER_BITS, OTHER_K>) -> Result<(), String> {
+        if ( BITS, K ) != ( OTHER_BITS, OTHER_K ) {
+            return Err(format!("Cannot merge BloomFilter with {} bits and {} hashes into one with {} bits and {} hashes.",
+                OTHER_BITS, OTHER_K, BITS, K));
+        }
+        Union::<BitSet>::merge_in(&mut val.bits, other.bits);
+        Ok(())
+    }
+
+    // Double hashing: the `i`th index is `h1 + i * h2`.
+    fn indices<T: Hash + ?Sized>(item: &T) -> impl Iterator<Item = usize> {
+        let h1 = sketch_hash(item);
+        let h2 = sketch_hash(&h1) | 1;
+        (0..K as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % BITS as u64) as usize)
+    }
+}
+impl <const BITS: usize, const K: u32> Default for BloomFilter<BITS, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl <const BITS: usize, const K: u32> Merge for BloomFilter<BITS, K> {
+    type Domain = Self;
+
+    fn merge_in(val: &mut Self, other: Self) {
+        Union::<BitSet>::merge_in(&mut val.bits, other.bits);
+    }
+
+    fn partial_cmp(val: &Self, other: &Self) -> Option<Ordering> {
+        Union::<BitSet>::partial_cmp(&val.bits, &other.bits)
+    }
+}
+impl <const BITS: usize, const K: u32> Bottom for BloomFilter<BITS, K> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+}
+
+
+
+
+
+
+
+// INTERVAL MERGES //
+
+/// Interval `[lo, hi]` (as `(lo, hi)`, with `lo <= hi`), merged by convex hull
+/// and ordered by containment. Use `WithBot<Interval<T>>` for an empty interval.
+pub struct Interval<T: Ord> {
+    _phantom: std::marker::PhantomData<T>,
+}
+impl <T: Ord> Merge for Interval<T> {
+    type Domain = (T, T);
+
+    fn merge_in(val: &mut Self::Domain, other: Self::Domain) {
+        <Pair<Min<T>, Max<T>> as Merge>::merge_in(val, other);
+    }
+
+    fn partial_cmp(val: &Self::Domain, other: &Self::Domain) -> Option<Ordering> {
+        <Pair<Min<T>, Max<T>> as Merge>::partial_cmp(val, other)
+    }
+}
+impl <T: Ord> Widen for Interval<T>
+where
+    Max<T>: Merge<Domain = T> + Bottom + Top,
+{
+    fn widen_in(val: &mut Self::Domain, other: Self::Domain) {
+        if other.0 < val.0 {
+            val.0 = Max::<T>::bottom();
+        }
+        if other.1 > val.1 {
+            val.1 = Max::<T>::top();
+        }
+    }
+
+    fn narrow_in(val: &mut Self::Domain, other: Self::Domain) {
+        if val.0 == Max::<T>::bottom() {
+            val.0 = other.0;
+        }
+        if val.1 == Max::<T>::top() {
+            val.1 = other.1;
+        }
+    }
+}
+impl <T: Ord> Top for Interval<T>
+where
+    Max<T>: Merge<Domain = T> + Bottom + Top,
+{
+    fn top() -> Self::Domain {
+        ( Max::<T>::bottom(), Max::<T>::top() )
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Signed numeric types, for `RangeToZero`. Sealed, since for unsigned or
+/// non-numeric types "closest to zero" would silently mean "smallest".
+pub trait Signed: PartialOrd + Default + sealed::Sealed {}
+macro_rules! signed_impl {
+    ( $( $t:ty ),+ ) => {
+        $(
+            impl sealed::Sealed for $t {}
+            impl Signed for $t {}
+        )+
+    };
+}
+signed_impl!(i8, i16, i32, i64, i128, isize, f32, f64);
+
+// Mingwei's weird semilattice.
+// Merge is defined as, given signed numbers A and B, take the value in the
+// range [A, B] (or [B, A]) which is closest to zero.
+// (Note that in general this will be A, B, or zero).
+// Works for any signed integer or float (see `Signed`), with `T::default()`
+// as zero. NaN merges with anything else to zero.
+pub struct RangeToZero<T: Signed> {
+    _phantom: std::marker::PhantomData<T>,
+}
+impl <T: Signed> Merge for RangeToZero<T> {
+    type Domain = T;
+
+    fn merge_in(val: &mut T, other: T) {
+        let zero = T::default();
+        let sign = PartialOrd::partial_cmp(&*val, &zero);
+        if sign != other.partial_cmp(&zero) {
+            *val = zero;
+        }
+        else if (Some(Ordering::Greater) == sign && other < *val)
+            || (Some(Ordering::Less) == sign && other > *val)
+        {
+            *val = other;
+        }
+    }
+
+    fn partial_cmp(val: &T, other: &T) -> Option<Ordering> {
+        let zero = T::default();
+        let sign = val.partial_cmp(&zero);
+        if sign != other.partial_cmp(&zero) {
+            // Zero is the top, comparable to everything.
+            if zero == *val {
+                Some(Ordering::Greater)
+            }
+            else if zero == *other {
+                Some(Ordering::Less)
+            }
+            else {
+                None
+            }
+        }
+        else {
+            match sign {
+                Some(Ordering::Greater) => other.partial_cmp(val),
+                Some(Ordering::Less) => val.partial_cmp(other),
+                // Zero, or NaN.
+                _ => Some(Ordering::Equal),
+            }
+        }
+    }
+}
+// Both the least and greatest values are minimal, so there is no bottom.
+impl <T: Signed> Top for RangeToZero<T> {
+    fn top() -> T {
+        T::default()
+    }
+}
+
+pub type RangeToZeroI32 = RangeToZero<i32>;
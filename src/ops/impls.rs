@@ -10,7 +10,7 @@ use tokio::sync::mpsc;
 
 // // use tokio::stream::Stream;
 
-use crate::merge::Merge;
+use crate::merge::{ Merge, Bottom, DeltaMerge, MergeRef };
 // use crate::semilattice::Semilattice;
 
 use super::*;
@@ -184,12 +184,25 @@ pub struct LatticeOp<F: Merge, P: ExclRefOp<Domain = F::Domain>> {
     next_pipe: P,
 }
 impl<F: Merge, P: ExclRefOp<Domain = F::Domain>> LatticeOp<F, P> {
-    pub fn new(bottom: F::Domain, next_pipe: P) -> Self {
+    /// Starts from the given `value`, which should be `F`'s bottom unless
+    /// resuming from existing state.
+    pub fn from_value(value: F::Domain, next_pipe: P) -> Self {
         LatticeOp {
-            value: bottom,
+            value: value,
             next_pipe: next_pipe,
         }
     }
+
+    /// Merges in a whole batch, then pushes the merged value downstream once.
+    pub fn push_all(&mut self, items: impl IntoIterator<Item = F::Domain>) -> P::Feedback {
+        F::merge_all(&mut self.value, items);
+        self.next_pipe.push(&self.value)
+    }
+}
+impl<F: Merge + Bottom, P: ExclRefOp<Domain = F::Domain>> LatticeOp<F, P> {
+    pub fn new(next_pipe: P) -> Self {
+        Self::from_value(F::bottom(), next_pipe)
+    }
 }
 impl<F: Merge, P: ExclRefOp<Domain = F::Domain>> Op for LatticeOp<F, P> {
     type Domain = F::Domain;
@@ -205,6 +218,80 @@ impl<F: Merge, P: ExclRefOp<Domain = F::Domain>> ExclMoveOp for LatticeOp<F, P>
 
 
 
+/// `LatticeOp` taking items by reference, e.g. from a `TeeOp` or `SplitOp`
+/// fan-out, without cloning each whole item first.
+pub struct RefLatticeOp<F: MergeRef, P: ExclRefOp<Domain = F::Domain>> {
+    value: F::Domain,
+    next_pipe: P,
+}
+impl<F: MergeRef, P: ExclRefOp<Domain = F::Domain>> RefLatticeOp<F, P> {
+    /// Starts from the given `value`, which should be `F`'s bottom unless
+    /// resuming from existing state.
+    pub fn from_value(value: F::Domain, next_pipe: P) -> Self {
+        RefLatticeOp {
+            value: value,
+            next_pipe: next_pipe,
+        }
+    }
+}
+impl<F: MergeRef + Bottom, P: ExclRefOp<Domain = F::Domain>> RefLatticeOp<F, P> {
+    pub fn new(next_pipe: P) -> Self {
+        Self::from_value(F::bottom(), next_pipe)
+    }
+}
+impl<F: MergeRef, P: ExclRefOp<Domain = F::Domain>> Op for RefLatticeOp<F, P> {
+    type Domain = F::Domain;
+}
+impl<F: MergeRef, P: ExclRefOp<Domain = F::Domain>> ExclRefOp for RefLatticeOp<F, P> {
+    type Feedback = P::Feedback;
+
+    fn push(&mut self, item: &Self::Domain) -> Self::Feedback {
+        F::merge_in_ref(&mut self.value, item);
+        self.next_pipe.push(&self.value)
+    }
+}
+
+
+
+pub struct DeltaLatticeOp<F: DeltaMerge, P: ExclMoveOp<Domain = F::Domain>> {
+    value: F::Domain,
+    next_pipe: P,
+}
+impl<F: DeltaMerge, P: ExclMoveOp<Domain = F::Domain>> DeltaLatticeOp<F, P> {
+    /// Starts from the given `value`, which should be `F`'s bottom unless
+    /// resuming from existing state.
+    pub fn from_value(value: F::Domain, next_pipe: P) -> Self {
+        DeltaLatticeOp {
+            value: value,
+            next_pipe: next_pipe,
+        }
+    }
+}
+impl<F: DeltaMerge + Bottom, P: ExclMoveOp<Domain = F::Domain>> DeltaLatticeOp<F, P> {
+    pub fn new(next_pipe: P) -> Self {
+        Self::from_value(F::bottom(), next_pipe)
+    }
+}
+impl<F: DeltaMerge, P: ExclMoveOp<Domain = F::Domain>> Op for DeltaLatticeOp<F, P> {
+    type Domain = F::Domain;
+}
+impl<F: DeltaMerge, P: ExclMoveOp<Domain = F::Domain>> ExclMoveOp for DeltaLatticeOp<F, P> {
+    type Feedback = impl Future;
+
+    // Only pushes the delta, and only if the value changed.
+    fn push(&mut self, item: Self::Domain) -> Self::Feedback {
+        if let Some(delta) = F::merge_in_delta(&mut self.value, item) {
+            Either::Left(self.next_pipe.push(delta)
+                .map(|x| Some(x)))
+        }
+        else {
+            Either::Right(future::ready(None))
+        }
+    }
+}
+
+
+
 pub struct MpscOp<T: 'static> {
     sender: mpsc::Sender<T>,
 }
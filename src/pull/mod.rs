@@ -1,6 +1,6 @@
 use std::task::{ Context, Poll };
 
-use crate::merge::Merge;
+use crate::merge::{ Merge, Bottom };
 
 
 pub trait PullOp {
@@ -49,26 +49,41 @@ where
 
 
 
+/// Most deltas merged per `poll_next`, so an always-ready upstream cannot
+/// keep a single poll from returning.
+pub const MAX_BATCH: usize = 1024;
+
 pub struct LatticeOp<St: MovePullOp, F: Merge<Domain = St::Domain>> {
     stream: St,
     state: F::Domain,
 }
 impl<St: MovePullOp, F: Merge<Domain = St::Domain>> LatticeOp<St, F> {
-    pub fn new(stream: St, bottom: F::Domain) -> Self {
+    /// Starts from the given `state`, which should be `F`'s bottom unless
+    /// resuming from existing state.
+    pub fn from_value(stream: St, state: F::Domain) -> Self {
         Self {
             stream: stream,
-            state: bottom,
+            state: state,
         }
     }
 }
+impl<St: MovePullOp, F: Merge<Domain = St::Domain> + Bottom> LatticeOp<St, F> {
+    pub fn new(stream: St) -> Self {
+        Self::from_value(stream, F::bottom())
+    }
+}
 impl<St: MovePullOp, F: Merge<Domain = St::Domain>> PullOp for LatticeOp<St, F> {
     type Domain = St::Domain;
 }
 impl<St: MovePullOp, F: Merge<Domain = St::Domain>> RefPullOp for LatticeOp<St, F> {
     fn poll_next(&mut self, ctx: &mut Context<'_>) -> Poll<Option<&Self::Domain>> {
-        if let Poll::Ready(Some(delta)) = self.stream.poll_next(ctx) {
-            F::merge_in(&mut self.state, delta);
-        }
+        // Coalesce the deltas that are ready into one batch.
+        let stream = &mut self.stream;
+        let deltas = std::iter::from_fn(|| match stream.poll_next(ctx) {
+            Poll::Ready(Some(delta)) => Some(delta),
+            _ => None,
+        }).take(MAX_BATCH);
+        F::merge_all(&mut self.state, deltas);
         Poll::Ready(Some(&self.state))
     }
 }
@@ -1,4 +1,4 @@
-use crate::merge::Merge;
+use crate::merge::{ Merge, Bottom };
 
 
 // LATTICE STRUCT //
@@ -28,6 +28,12 @@ impl <F: Merge> Semilattice<F> {
     }
 }
 
+impl <F: Merge + Bottom> Semilattice<F> {
+    pub fn bottom() -> Self {
+        Self::new(F::bottom())
+    }
+}
+
 impl <F: Merge> Default for Semilattice<F>
 where
     F::Domain: Default,
@@ -0,0 +1,280 @@
+use std::collections::{ BTreeMap, BTreeSet, HashMap, HashSet };
+
+use spinach::laws::{ check_laws, check_laws_arbitrary, check_laws_batch, check_laws_batch_by, check_lattice_laws, check_lattice_laws_batch_by, check_lattice_laws_arbitrary, check_merge_all_arbitrary, check_merge_ref_arbitrary };
+use spinach::merge::{ Merge, And, Dual, DominatingPair, FloatMax, FloatMin, Intersect, Interval, Lexicographic, MapIntersection, MapUnion, Max, Min, Or, Pair, Pointwise, RangeToZero, RangeToZeroI32, Union, WithBot, WithTop };
+use spinach::merge::{ NanIsBottom, NanIsTop, TotalOrder };
+use spinach::merge::{ BitSet, BloomFilter, HyperLogLog, SortedVec };
+use spinach::merge::{ AddWins, Bias, GCounter, LwwElementSet, LwwRegister, MvRegister, OrSet, PnCounter, RemoveWins, TwoPhaseSet, VClock };
+
+
+#[test]
+pub fn test_ord_laws() -> Result<(), String> {
+    check_laws_arbitrary::<Max<u8>>()?;
+    check_laws_arbitrary::<Max<String>>()?;
+    check_laws_arbitrary::<Min<i16>>()?;
+    check_laws_arbitrary::<RangeToZeroI32>()?;
+    check_laws_arbitrary::<RangeToZero<i8>>()?;
+    check_laws_arbitrary::<RangeToZero<i128>>()?;
+    check_laws::<RangeToZero<f64>, i16>(|x| x as f64 / 4.0)?;
+    check_laws::<Interval<i8>, ( i8, i8 )>(|( a, b )| ( a.min(b), a.max(b) ))?;
+    check_laws::<WithBot<Interval<u8>>, Option<( u8, u8 )>>(|x| x.map(|( a, b )| ( a.min(b), a.max(b) )))?;
+
+    check_laws::<DominatingPair<FloatMax<f32, TotalOrder>, Max<u8>>, ( i8, u8 )>(|( x, y )| ( x as f32, y ))?;
+    Ok(())
+}
+
+/// Arbitrary floats plus the special values: NaNs of both signs and two
+/// payloads, signed zeros, and infinities.
+fn float_samples(seeds: Vec<i16>) -> Vec<f64> {
+    let mut samples = vec![
+        f64::NAN, -f64::NAN, f64::from_bits(f64::NAN.to_bits() | 1),
+        0.0, -0.0, f64::INFINITY, f64::NEG_INFINITY,
+    ];
+    samples.extend(seeds.into_iter().map(|x| x as f64 / 4.0));
+    samples
+}
+
+#[test]
+pub fn test_float_laws() -> Result<(), String> {
+    // `==` is not an equivalence on floats, so compare bits.
+    let same_bits = |a: &f64, b: &f64| a.to_bits() == b.to_bits();
+    check_lattice_laws_batch_by::<FloatMax<f64, NanIsBottom>, i16>(float_samples, same_bits)?;
+    check_lattice_laws_batch_by::<FloatMax<f64, NanIsTop>, i16>(float_samples, same_bits)?;
+    check_lattice_laws_batch_by::<FloatMax<f64, TotalOrder>, i16>(float_samples, same_bits)?;
+    check_laws_batch_by::<FloatMin<f64, NanIsBottom>, i16>(float_samples, same_bits)?;
+
+    let float_samples = |seeds| float_samples(seeds).into_iter().map(|x| x as f32).collect();
+    let same_bits = |a: &f32, b: &f32| a.to_bits() == b.to_bits();
+    check_lattice_laws_batch_by::<FloatMax<f32, NanIsBottom>, i16>(float_samples, same_bits)?;
+    check_lattice_laws_batch_by::<FloatMax<f32, NanIsTop>, i16>(float_samples, same_bits)?;
+    check_lattice_laws_batch_by::<FloatMax<f32, TotalOrder>, i16>(float_samples, same_bits)?;
+    Ok(())
+}
+
+#[test]
+pub fn test_set_laws() -> Result<(), String> {
+    check_laws_arbitrary::<Union<HashSet<u8>>>()?;
+    check_laws_arbitrary::<Union<BTreeSet<u8>>>()?;
+    check_laws_arbitrary::<Intersect<HashSet<u8>>>()?;
+    check_laws_arbitrary::<Intersect<BTreeSet<u8>>>()?;
+    check_laws::<Union<SortedVec<u8>>, Vec<u8>>(SortedVec::from)?;
+    check_laws::<Intersect<SortedVec<u8>>, Vec<u8>>(SortedVec::from)?;
+    check_laws::<Union<BitSet>, Vec<u8>>(|items| items.into_iter().map(usize::from).collect())?;
+    check_laws::<Intersect<BitSet>, Vec<u8>>(|items| items.into_iter().map(usize::from).collect())?;
+    Ok(())
+}
+
+#[test]
+pub fn test_map_laws() -> Result<(), String> {
+    check_laws_arbitrary::<MapUnion<HashMap<u8, Max<u8>>>>()?;
+    check_laws_arbitrary::<MapUnion<BTreeMap<u8, Union<BTreeSet<u8>>>>>()?;
+    check_laws_arbitrary::<MapIntersection<HashMap<u8, Max<u8>>>>()?;
+    check_laws_arbitrary::<MapIntersection<BTreeMap<u8, Union<BTreeSet<u8>>>>>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_merge_ref_laws() -> Result<(), String> {
+    check_merge_ref_arbitrary::<Max<String>>()?;
+    check_merge_ref_arbitrary::<Min<u8>>()?;
+    check_merge_ref_arbitrary::<Union<HashSet<u8>>>()?;
+    check_merge_ref_arbitrary::<Union<BTreeSet<u8>>>()?;
+    check_merge_ref_arbitrary::<Intersect<HashSet<u8>>>()?;
+    check_merge_ref_arbitrary::<Intersect<BTreeSet<u8>>>()?;
+    check_merge_ref_arbitrary::<MapUnion<HashMap<u8, Union<HashSet<u8>>>>>()?;
+    check_merge_ref_arbitrary::<MapUnion<BTreeMap<u8, Max<u8>>>>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_merge_all_laws() -> Result<(), String> {
+    check_merge_all_arbitrary::<Max<u8>>()?;
+    check_merge_all_arbitrary::<Union<BTreeSet<u8>>>()?;
+    check_merge_all_arbitrary::<Intersect<HashSet<u8>>>()?;
+    check_merge_all_arbitrary::<Intersect<BTreeSet<u8>>>()?;
+    check_merge_all_arbitrary::<MapUnion<BTreeMap<u8, Max<u8>>>>()?;
+    check_merge_all_arbitrary::<MapUnion<BTreeMap<u8, Union<BTreeSet<u8>>>>>()?;
+    check_merge_all_arbitrary::<Dual<Intersect<BTreeSet<u8>>>>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_product_laws() -> Result<(), String> {
+    check_laws_arbitrary::<Pair<Max<u8>, Min<u8>>>()?;
+    check_laws_arbitrary::<( Max<u8>, Union<BTreeSet<u8>>, Min<u8> )>()?;
+    check_laws_arbitrary::<MapUnion<HashMap<u8, ( Max<u8>, Min<u8> )>>>()?;
+    check_lattice_laws_arbitrary::<Pointwise<Vec<Max<u8>>>>()?;
+    check_lattice_laws_arbitrary::<Pointwise<Vec<Union<BTreeSet<u8>>>>>()?;
+    check_lattice_laws_arbitrary::<Pointwise<[Min<u8>; 3]>>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_meet_laws() -> Result<(), String> {
+    check_lattice_laws_arbitrary::<Max<i8>>()?;
+    check_lattice_laws_arbitrary::<Min<u8>>()?;
+    check_lattice_laws_arbitrary::<Union<HashSet<u8>>>()?;
+    check_lattice_laws_arbitrary::<Intersect<BTreeSet<u8>>>()?;
+    check_lattice_laws::<Union<SortedVec<u8>>, Vec<u8>>(SortedVec::from)?;
+    check_lattice_laws::<Union<BitSet>, Vec<u8>>(|items| items.into_iter().map(usize::from).collect())?;
+    check_lattice_laws_arbitrary::<Dual<Dual<Union<BTreeSet<u8>>>>>()?;
+    check_lattice_laws_arbitrary::<MapUnion<HashMap<u8, Max<u8>>>>()?;
+    check_lattice_laws_arbitrary::<MapUnion<BTreeMap<u8, Intersect<HashSet<u8>>>>>()?;
+    check_lattice_laws_arbitrary::<MapIntersection<HashMap<u8, Min<u8>>>>()?;
+    check_lattice_laws_arbitrary::<MapIntersection<BTreeMap<u8, Union<BTreeSet<u8>>>>>()?;
+    check_lattice_laws_arbitrary::<Pair<Max<u8>, Intersect<HashSet<u8>>>>()?;
+    check_lattice_laws_arbitrary::<( Min<u8>, Max<char>, Union<HashSet<u8>> )>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_adapter_laws() -> Result<(), String> {
+    check_laws_arbitrary::<WithBot<Max<String>>>()?;
+    check_laws_arbitrary::<WithBot<DominatingPair<Max<u8>, Min<u8>>>>()?;
+    check_laws_arbitrary::<MapUnion<HashMap<u8, WithTop<Union<BTreeSet<u8>>>>>>()?;
+    check_lattice_laws_arbitrary::<Or>()?;
+    check_lattice_laws_arbitrary::<And>()?;
+    check_lattice_laws_arbitrary::<WithBot<Pair<Or, Min<u8>>>>()?;
+    check_lattice_laws_arbitrary::<WithTop<Intersect<HashSet<u8>>>>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_lexicographic_laws() -> Result<(), String> {
+    // `DominatingPair` needs a chain first.
+    check_laws_arbitrary::<DominatingPair<Max<u8>, Union<HashSet<u8>>>>()?;
+    check_laws_arbitrary::<DominatingPair<Min<u8>, MapUnion<BTreeMap<u8, Max<u8>>>>>()?;
+    check_laws_arbitrary::<Lexicographic<Max<u8>, Union<HashSet<u8>>>>()?;
+    check_laws_arbitrary::<Lexicographic<Union<BTreeSet<u8>>, Max<u8>>>()?;
+    check_laws_arbitrary::<Lexicographic<MapUnion<HashMap<u8, Max<u8>>>, Union<BTreeSet<u8>>>>()?;
+    Ok(())
+}
+
+#[test]
+pub fn test_crdt_laws() -> Result<(), String> {
+    check_laws::<VClock<u8>, HashMap<u8, u64>>(VClock::from)?;
+
+    check_laws::<GCounter<u8>, Vec<( u8, u8 )>>(|incs| {
+        let mut counter = GCounter::new();
+        for ( replica, n ) in incs {
+            counter.increment(replica % 4, n.into());
+        }
+        counter
+    })?;
+
+    check_laws::<PnCounter<u8>, Vec<( u8, u8, bool )>>(|ops| {
+        let mut counter = PnCounter::new();
+        for ( replica, n, inc ) in ops {
+            if inc {
+                counter.increment(replica % 4, n.into());
+            }
+            else {
+                counter.decrement(replica % 4, n.into());
+            }
+        }
+        counter
+    })?;
+
+    check_laws::<TwoPhaseSet<u8>, Vec<( u8, bool )>>(|ops| {
+        let mut set = TwoPhaseSet::new();
+        for ( value, insert ) in ops {
+            if insert {
+                set.insert(value % 8);
+            }
+            else {
+                set.remove(value % 8);
+            }
+        }
+        set
+    })?;
+
+    check_laws::<OrSet<u8, u8>, Vec<( u8, u8, bool )>>(|ops| {
+        let mut set = OrSet::new();
+        for ( node, value, insert ) in ops {
+            if insert {
+                set.insert(node % 3, value % 8);
+            }
+            else {
+                set.remove(&(value % 8));
+            }
+        }
+        set
+    })?;
+
+    fn lww_element_set<B: Bias>(ops: Vec<( u8, u8, bool )>) -> LwwElementSet<u8, u8, B> {
+        let mut set = LwwElementSet::new();
+        for ( value, ts, insert ) in ops {
+            if insert {
+                set.insert(value % 8, ts % 8);
+            }
+            else {
+                set.remove(value % 8, ts % 8);
+            }
+        }
+        set
+    }
+    check_laws::<LwwElementSet<u8, u8, AddWins>, _>(lww_element_set)?;
+    check_laws::<LwwElementSet<u8, u8, RemoveWins>, _>(lww_element_set)?;
+
+    // Few timestamps, so concurrent writes collide and must break ties.
+    check_laws::<LwwRegister<u8, u8>, ( u8, u8 )>(|( ts, value )| LwwRegister::new(ts % 4, value))?;
+
+    Ok(())
+}
+
+#[test]
+pub fn test_mv_register_laws() -> Result<(), String> {
+    // Clocks are only unique within one history, so samples are the states
+    // seen by replicas writing and syncing with each other.
+    check_laws_batch::<MvRegister<u8, u8>, ( u8, u8, Option<u8> )>(|ops| {
+        let mut replicas = vec![ MvRegister::new(); 3 ];
+        let mut samples = Vec::new();
+        for ( replica, value, sync_from ) in ops {
+            let replica = (replica % 3) as usize;
+            match sync_from {
+                Some(other) => {
+                    let other = replicas[(other % 3) as usize].clone();
+                    MvRegister::merge_in(&mut replicas[replica], other);
+                },
+                None => replicas[replica].write(replica as u8, value),
+            }
+            samples.push(replicas[replica].clone());
+        }
+        samples
+    })
+}
+
+#[test]
+pub fn test_sketch_laws() -> Result<(), String> {
+    check_laws::<HyperLogLog<4>, Vec<u16>>(|items| {
+        let mut hll = HyperLogLog::new();
+        for item in items {
+            hll.insert(&item);
+        }
+        hll
+    })?;
+    check_laws::<BloomFilter<64, 3>, Vec<u16>>(|items| {
+        let mut bloom = BloomFilter::new();
+        for item in items {
+            bloom.insert(&item);
+        }
+        bloom
+    })
+}
+
+#[test]
+pub fn test_generator_laws() -> Result<(), String> {
+    check_laws_arbitrary::<Union<SortedVec<u8>>>()?;
+    check_lattice_laws_arbitrary::<Union<BitSet>>()?;
+    check_laws_arbitrary::<VClock<u8>>()?;
+    check_laws_arbitrary::<LwwRegister<bool, u8>>()?;
+    check_laws_arbitrary::<OrSet<u8, bool>>()?;
+    check_laws_arbitrary::<GCounter<u8>>()?;
+    check_laws_arbitrary::<PnCounter<u8>>()?;
+    check_laws_arbitrary::<TwoPhaseSet<u8>>()?;
+    check_laws_arbitrary::<LwwElementSet<u8, bool, AddWins>>()?;
+    check_laws_arbitrary::<LwwElementSet<u8, bool, RemoveWins>>()?;
+    check_laws_arbitrary::<HyperLogLog<4>>()?;
+    check_laws_arbitrary::<BloomFilter<64, 3>>()?;
+    Ok(())
+}
@@ -0,0 +1,848 @@
+use std::cmp::Ordering;
+use std::collections::{ BTreeMap, BTreeSet, HashMap, HashSet };
+
+use spinach::collections::MapLike;
+use spinach::laws;
+
+use spinach::merge::{ Merge, Bottom, DeltaMerge, Meet, MergeRef, Top, Widen };
+use spinach::merge::{ And, Dual, DominatingPair, FloatMax, FloatMin, Intersect, Interval, Lexicographic, MapIntersection, MapUnion, Max, Min, Or, Pair, Pointwise, RangeToZero, Union, WithBot, WithTop };
+use spinach::merge::{ NanIsBottom, NanIsTop, TotalOrder };
+use spinach::merge::{ BitSet, BloomFilter, HyperLogLog, SortedVec, bloom_filter_params };
+use spinach::merge::{ AddWins, Causality, GCounter, LwwElementSet, LwwRegister, MvRegister, OrSet, PnCounter, RemoveWins, TwoPhaseSet, VClock };
+
+
+#[test]
+pub fn test_pair() {
+    type F = Pair<Max<u64>, Union<HashSet<&'static str>>>;
+
+    let mut val = ( 1, vec![ "a" ].into_iter().collect() );
+    F::merge_in(&mut val, ( 3, vec![ "b" ].into_iter().collect() ));
+    assert_eq!(( 3, vec![ "a", "b" ].into_iter().collect() ), val);
+
+    let small = ( 1, vec![ "a" ].into_iter().collect() );
+    let big = ( 2, vec![ "a", "b" ].into_iter().collect() );
+    let mixed = ( 3, vec![ "c" ].into_iter().collect() );
+    assert_eq!(Some(Ordering::Equal), F::partial_cmp(&small, &small));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&small, &big));
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&big, &small));
+    assert_eq!(None, F::partial_cmp(&big, &mixed));
+}
+
+#[test]
+pub fn test_tuple() {
+    type F = ( Max<u32>, Min<u32>, Max<char> );
+
+    let mut val = ( 1, 5, 'b' );
+    F::merge_in(&mut val, ( 0, 2, 'c' ));
+    assert_eq!(( 1, 2, 'c' ), val);
+
+    assert_eq!(Some(Ordering::Equal), F::partial_cmp(&( 1, 5, 'b' ), &( 1, 5, 'b' )));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&( 1, 5, 'b' ), &( 1, 4, 'b' )));
+    assert_eq!(None, F::partial_cmp(&( 1, 5, 'b' ), &( 2, 6, 'b' )));
+}
+
+#[test]
+pub fn test_sorted_vec() {
+    type F = Union<SortedVec<u32>>;
+
+    let mut val: SortedVec<_> = vec![ 5, 1, 3, 1 ].into();
+    assert_eq!(&[ 1, 3, 5 ], val.as_slice());
+    F::merge_in(&mut val, vec![ 2, 3, 8 ].into());
+    assert_eq!(&[ 1, 2, 3, 5, 8 ], val.as_slice());
+    F::merge_in(&mut val, vec![ 9, 10 ].into());
+    assert_eq!(&[ 1, 2, 3, 5, 8, 9, 10 ], val.as_slice());
+    assert!(val.contains(&8));
+    assert!(!val.insert(8));
+
+    let small: SortedVec<_> = vec![ 2, 5 ].into();
+    let mixed: SortedVec<_> = vec![ 2, 4 ].into();
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&val, &small));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&small, &val));
+    assert_eq!(None, F::partial_cmp(&val, &mixed));
+    assert_eq!(None, F::partial_cmp(&small, &mixed));
+
+    Intersect::<SortedVec<_>>::merge_in(&mut val, vec![ 0, 2, 4, 5, 10 ].into());
+    assert_eq!(&[ 2, 5, 10 ], val.as_slice());
+
+    // No `T: Default` needed (`Ordering` has none).
+    assert!(SortedVec::<Ordering>::default().as_slice().is_empty());
+}
+
+#[test]
+pub fn test_bit_set() {
+    type F = Union<BitSet>;
+
+    let mut val: BitSet = vec![ 1, 64, 200 ].into_iter().collect();
+    F::merge_in(&mut val, vec![ 2, 64 ].into_iter().collect());
+    assert_eq!(vec![ 1, 2, 64, 200 ], val.iter().collect::<Vec<_>>());
+    assert_eq!(4, val.len());
+    assert!(val.contains(200));
+    assert!(!val.contains(1000));
+
+    let small: BitSet = vec![ 2, 200 ].into_iter().collect();
+    let mixed: BitSet = vec![ 3 ].into_iter().collect();
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&val, &small));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&small, &val));
+    assert_eq!(None, F::partial_cmp(&val, &mixed));
+
+    // Removing the largest element shrinks the words, keeping equality exact.
+    assert!(val.remove(200));
+    assert_eq!(2, val.words().len());
+    Intersect::<BitSet>::merge_in(&mut val, small);
+    assert_eq!(vec![ 2 ].into_iter().collect::<BitSet>(), val);
+    Intersect::<BitSet>::merge_in(&mut val, mixed);
+    assert!(val.is_empty());
+    assert_eq!(BitSet::new(), val);
+}
+
+#[test]
+pub fn test_map_union_partial_cmp() {
+    type F = MapUnion<HashMap<&'static str, Max<u32>>>;
+    let map = |entries: &[( &'static str, u32 )]| entries.iter().copied().collect::<HashMap<_, _>>();
+
+    // Equal maps.
+    assert_eq!(Some(Ordering::Equal), F::partial_cmp(&map(&[]), &map(&[])));
+    assert_eq!(Some(Ordering::Equal), F::partial_cmp(&map(&[ ( "a", 1 ), ( "b", 2 ) ]), &map(&[ ( "a", 1 ), ( "b", 2 ) ])));
+
+    // Same keys, values all in one direction.
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&map(&[ ( "a", 1 ), ( "b", 2 ) ]), &map(&[ ( "a", 1 ), ( "b", 3 ) ])));
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&map(&[ ( "a", 2 ), ( "b", 2 ) ]), &map(&[ ( "a", 1 ), ( "b", 2 ) ])));
+
+    // More keys, with values equal or in the same direction.
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&map(&[ ( "a", 1 ), ( "b", 2 ) ]), &map(&[ ( "a", 1 ) ])));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&map(&[ ( "a", 1 ) ]), &map(&[ ( "a", 2 ), ( "b", 2 ) ])));
+
+    // Values comparing in mixed directions are incomparable.
+    assert_eq!(None, F::partial_cmp(&map(&[ ( "a", 1 ), ( "b", 3 ) ]), &map(&[ ( "a", 2 ), ( "b", 2 ) ])));
+    // As are more keys with a smaller value, and disjoint keys.
+    assert_eq!(None, F::partial_cmp(&map(&[ ( "a", 1 ), ( "b", 2 ) ]), &map(&[ ( "a", 2 ) ])));
+    assert_eq!(None, F::partial_cmp(&map(&[ ( "a", 2 ) ]), &map(&[ ( "a", 1 ), ( "b", 2 ) ])));
+    assert_eq!(None, F::partial_cmp(&map(&[ ( "a", 1 ) ]), &map(&[ ( "b", 1 ) ])));
+}
+
+#[test]
+pub fn test_map_intersection() {
+    type F = MapIntersection<HashMap<&'static str, Max<u32>>>;
+
+    let mut val: HashMap<_, _> = vec![ ( "a", 1 ), ( "b", 5 ) ].into_iter().collect();
+    F::merge_in(&mut val, vec![ ( "b", 3 ), ( "c", 2 ) ].into_iter().collect());
+    assert_eq!(vec![ ( "b", 5 ) ].into_iter().collect::<HashMap<_, _>>(), val);
+
+    let ab: HashMap<_, _> = vec![ ( "a", 1 ), ( "b", 1 ) ].into_iter().collect();
+    let b: HashMap<_, _> = vec![ ( "b", 2 ) ].into_iter().collect();
+    let c: HashMap<_, _> = vec![ ( "c", 2 ) ].into_iter().collect();
+    assert_eq!(Some(Ordering::Equal), F::partial_cmp(&ab, &ab));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&ab, &b));
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&b, &ab));
+    assert_eq!(None, F::partial_cmp(&b, &c));
+
+    // Fewer keys, but a smaller value.
+    let b0: HashMap<_, _> = vec![ ( "b", 0 ) ].into_iter().collect();
+    assert_eq!(None, F::partial_cmp(&ab, &b0));
+}
+
+#[test]
+pub fn test_map_intersection_btree() {
+    type F = MapIntersection<BTreeMap<u32, Union<HashSet<char>>>>;
+
+    let mut val: BTreeMap<_, _> = vec![
+        ( 1, vec![ 'x' ].into_iter().collect() ),
+        ( 2, vec![ 'y' ].into_iter().collect() ),
+    ].into_iter().collect();
+    F::merge_in(&mut val, vec![
+        ( 2, vec![ 'z' ].into_iter().collect() ),
+        ( 3, vec![ 'w' ].into_iter().collect() ),
+    ].into_iter().collect());
+
+    let expected: BTreeMap<_, HashSet<_>> = vec![ ( 2, vec![ 'y', 'z' ].into_iter().collect() ) ].into_iter().collect();
+    assert_eq!(expected, val);
+}
+
+/// Small map kept as a `Vec` sorted by key, to test user collections.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VecMap<K, V> {
+    entries: Vec<(K, V)>,
+}
+impl <K, V> Default for VecMap<K, V> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+impl <K: Ord, V> VecMap<K, V> {
+    fn find(&self, key: &K) -> Result<usize, usize> {
+        self.entries.binary_search_by(|(k, _)| k.cmp(key))
+    }
+}
+impl <K, V> IntoIterator for VecMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+impl <K: Ord, V> std::iter::FromIterator<(K, V)> for VecMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self { entries: Vec::new() };
+        for (k, v) in iter {
+            map.insert(k, v);
+        }
+        map
+    }
+}
+impl <K: Ord, V> MapLike for VecMap<K, V> {
+    type Key = K;
+    type Val = V;
+    type Iter<'a> = std::iter::Map<std::slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)> where Self: 'a;
+    type Rebind<W> = VecMap<K, W>;
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.find(key).ok().map(|i| &self.entries[i].1)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let i = self.find(key).ok()?;
+        Some(&mut self.entries[i].1)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> Option<V> {
+        match self.find(&key) {
+            Ok(i) => Some(std::mem::replace(&mut self.entries[i].1, val)),
+            Err(i) => {
+                self.entries.insert(i, (key, val));
+                None
+            },
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let i = self.find(key).ok()?;
+        Some(self.entries.remove(i).1)
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.entries.retain_mut(|(k, v)| f(k, v));
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+#[test]
+pub fn test_custom_map() -> Result<(), String> {
+    type F = MapUnion<VecMap<&'static str, Max<u32>>>;
+
+    let mut val: VecMap<_, _> = vec![ ( "b", 1 ), ( "a", 5 ) ].into_iter().collect();
+    F::merge_in(&mut val, vec![ ( "b", 3 ), ( "c", 1 ) ].into_iter().collect());
+    assert_eq!(vec![ ( "a", 5 ), ( "b", 3 ), ( "c", 1 ) ], val.entries);
+
+    let small: VecMap<_, _> = vec![ ( "a", 2 ) ].into_iter().collect();
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&val, &small));
+    assert_eq!(None, MapIntersection::<VecMap<_, Max<u32>>>::partial_cmp(&val, &small));
+
+    let samples: Vec<VecMap<_, _>> = vec![
+        vec![],
+        vec![ ( "a", 1 ) ],
+        vec![ ( "a", 2 ), ( "b", 1 ) ],
+        vec![ ( "b", 2 ), ( "c", 0 ) ],
+    ].into_iter().map(|entries| entries.into_iter().collect()).collect();
+    laws::check_all_lattice::<F>(&samples)?;
+    laws::check_all::<MapIntersection<VecMap<_, Max<u32>>>>(&samples)
+}
+
+#[test]
+pub fn test_vclock() {
+    let mut a = VClock::new();
+    a.increment("a");
+    let mut b = a.clone();
+    assert_eq!(Causality::Equal, a.causality(&b));
+
+    b.increment("b");
+    assert!(a.happens_before(&b));
+    assert!(b.dominates(&a));
+    assert!(!a.dominates(&b));
+
+    a.increment("a");
+    assert!(a.concurrent_with(&b));
+    assert_eq!(Causality::Concurrent, b.causality(&a));
+
+    VClock::merge_in(&mut a, b.clone());
+    assert_eq!(2, a.get(&"a"));
+    assert_eq!(1, a.get(&"b"));
+    assert_eq!(Causality::HappensAfter, a.causality(&b));
+
+    // Zero counts are the same as missing nodes.
+    let zero = VClock::from(vec![ ( "a", 0 ) ].into_iter().collect::<HashMap<_, _>>());
+    assert_eq!(VClock::new(), zero);
+    assert_eq!(Causality::Equal, zero.causality(&VClock::new()));
+}
+
+#[test]
+pub fn test_lww_register() {
+    // Timestamps are ( time, replica id ).
+    let mut a = LwwRegister::new(( 1, 'a' ), "first");
+    let mut b = a.clone();
+
+    a.set(( 2, 'a' ), "from a");
+    b.set(( 2, 'b' ), "from b");
+    assert_eq!(Some(Ordering::Less), LwwRegister::partial_cmp(&a, &b));
+
+    let mut ab = a.clone();
+    LwwRegister::merge_in(&mut ab, b.clone());
+    let mut ba = b.clone();
+    LwwRegister::merge_in(&mut ba, a.clone());
+    assert_eq!(ab, ba);
+    assert_eq!(&"from b", ab.value());
+
+    // Stale writes are ignored.
+    ab.set(( 1, 'z' ), "stale");
+    assert_eq!(&( 2, 'b' ), ab.timestamp());
+    assert_eq!("from b", ab.into_value());
+
+    // Equal timestamps are broken by value, whichever arrives first.
+    let x = LwwRegister::new(5, "x");
+    let y = LwwRegister::new(5, "y");
+    let mut xy = x.clone();
+    LwwRegister::merge_in(&mut xy, y.clone());
+    let mut yx = y.clone();
+    LwwRegister::merge_in(&mut yx, x.clone());
+    assert_eq!(xy, yx);
+    assert_eq!(&"y", xy.value());
+    assert_eq!(Some(Ordering::Less), LwwRegister::partial_cmp(&x, &y));
+}
+
+#[test]
+pub fn test_mv_register() {
+    let mut a = MvRegister::new();
+    a.write("a", 1);
+    let mut b = a.clone();
+    assert_eq!(Some(Ordering::Equal), MvRegister::partial_cmp(&a, &b));
+
+    // Concurrent writes are both kept.
+    a.write("a", 2);
+    b.write("b", 3);
+    assert_eq!(None, MvRegister::partial_cmp(&a, &b));
+    let mut merged = a.clone();
+    MvRegister::merge_in(&mut merged, b.clone());
+    let mut siblings: Vec<_> = merged.siblings().cloned().collect();
+    siblings.sort();
+    assert_eq!(vec![ 2, 3 ], siblings);
+    assert_eq!(Some(Ordering::Greater), MvRegister::partial_cmp(&merged, &a));
+
+    // Writing supersedes the observed siblings.
+    merged.write("b", 4);
+    assert_eq!(vec![ 4 ], merged.siblings().cloned().collect::<Vec<_>>());
+    MvRegister::merge_in(&mut a, merged.clone());
+    assert_eq!(merged, a);
+    // Equal clocks with different values resolve to the greater value,
+    // whichever side it comes from.
+    let mut x = MvRegister::new();
+    x.write("a", 5);
+    let mut y = MvRegister::new();
+    y.write("a", 6);
+    assert_eq!(Some(Ordering::Less), MvRegister::partial_cmp(&x, &y));
+    let mut xy = x.clone();
+    MvRegister::merge_in(&mut xy, y.clone());
+    let mut yx = y.clone();
+    MvRegister::merge_in(&mut yx, x);
+    assert_eq!(xy, yx);
+    assert_eq!(y, xy);
+}
+
+#[test]
+pub fn test_or_set() {
+    let mut a = OrSet::new();
+    a.insert('a', "x");
+    a.insert('a', "y");
+    let mut b = a.clone();
+
+    // Concurrent remove and re-insert: the insert wins.
+    assert!(a.remove(&"x"));
+    assert!(!a.remove(&"x"));
+    b.insert('b', "x");
+    assert_eq!(None, OrSet::partial_cmp(&a, &b));
+
+    OrSet::merge_in(&mut a, b.clone());
+    assert!(a.contains(&"x"));
+    assert!(a.contains(&"y"));
+    assert_eq!(Some(Ordering::Greater), OrSet::partial_cmp(&a, &b));
+
+    // Observed remove.
+    a.remove(&"y");
+    OrSet::merge_in(&mut b, a.clone());
+    assert!(!b.contains(&"y"));
+    assert_eq!(vec![ &"x" ], b.iter().collect::<Vec<_>>());
+}
+
+#[test]
+pub fn test_counters() {
+    let mut a = GCounter::new();
+    a.increment("a", 3);
+    let mut b = a.clone();
+    b.increment("b", 2);
+    a.increment("a", 1);
+    assert_eq!(None, GCounter::partial_cmp(&a, &b));
+
+    GCounter::merge_in(&mut a, b.clone());
+    GCounter::merge_in(&mut a, b.clone());
+    assert_eq!(6, a.value());
+    assert_eq!(4, a.get(&"a"));
+    assert_eq!(Some(Ordering::Less), GCounter::partial_cmp(&b, &a));
+
+    let mut x = PnCounter::new();
+    x.increment("x", 5);
+    let mut y = x.clone();
+    y.decrement("y", 7);
+    assert_eq!(Some(Ordering::Greater), PnCounter::partial_cmp(&y, &x));
+    x.increment("x", 1);
+    assert_eq!(None, PnCounter::partial_cmp(&y, &x));
+
+    PnCounter::merge_in(&mut x, y);
+    assert_eq!(-1, x.value());
+
+    // Zero increments store nothing, so they stay `==` to the bottom.
+    let mut z = GCounter::new();
+    z.increment("z", 0);
+    assert_eq!(GCounter::new(), z);
+    assert_eq!(Some(Ordering::Equal), GCounter::partial_cmp(&z, &GCounter::new()));
+    let mut z = PnCounter::new();
+    z.increment("z", 0);
+    z.decrement("z", 0);
+    assert_eq!(PnCounter::new(), z);
+
+    // Totals beyond `i64` don't wrap.
+    let mut big = PnCounter::new();
+    big.increment("a", u64::MAX);
+    big.increment("b", u64::MAX);
+    assert_eq!(2 * u64::MAX as i128, big.value());
+    big.decrement("a", u64::MAX);
+    assert_eq!(u64::MAX as i128, big.value());
+    let mut neg = PnCounter::new();
+    neg.decrement("a", u64::MAX);
+    assert_eq!(-(u64::MAX as i128), neg.value());
+}
+
+#[test]
+pub fn test_two_phase_set() {
+    let mut a = TwoPhaseSet::new();
+    assert!(a.insert("x"));
+    assert!(a.insert("y"));
+    let mut b = a.clone();
+    b.remove("x");
+    assert_eq!(Some(Ordering::Less), TwoPhaseSet::partial_cmp(&a, &b));
+
+    TwoPhaseSet::merge_in(&mut a, b);
+    assert!(!a.contains(&"x"));
+    assert!(!a.insert("x"));
+    assert!(!a.contains(&"x"));
+    assert_eq!(vec![ &"y" ], a.iter().collect::<Vec<_>>());
+}
+
+#[test]
+pub fn test_lww_element_set() {
+    let mut a = LwwElementSet::<_, _, AddWins>::new();
+    a.insert("x", 1);
+    a.insert("y", 1);
+    let mut b = a.clone();
+    a.remove("x", 2);
+    b.insert("x", 3);
+    b.remove("y", 1);
+
+    let mut ab = a.clone();
+    LwwElementSet::merge_in(&mut ab, b.clone());
+    assert!(ab.contains(&"x"));
+    assert!(ab.contains(&"y"));
+    assert!(!ab.contains(&"z"));
+    assert_eq!(Some(Ordering::Greater), LwwElementSet::partial_cmp(&ab, &a));
+
+    // The same history with removes winning ties drops "y".
+    let mut c = LwwElementSet::<_, _, RemoveWins>::bottom();
+    c.insert("x", 1);
+    c.insert("y", 1);
+    let mut d = c.clone();
+    c.remove("x", 2);
+    d.insert("x", 3);
+    d.remove("y", 1);
+    LwwElementSet::merge_in(&mut c, d);
+    assert!(c.contains(&"x"));
+    assert!(!c.contains(&"y"));
+    assert_eq!(vec![ &"x" ], c.iter().collect::<Vec<_>>());
+}
+
+#[test]
+pub fn test_hyper_log_log() {
+    let mut a = HyperLogLog::<12>::new();
+    let mut b = HyperLogLog::<12>::new();
+    assert_eq!(0.0, a.estimate());
+    for i in 0..6000 {
+        a.insert(&i);
+    }
+    for i in 4000..10000 {
+        b.insert(&i);
+    }
+    HyperLogLog::merge_in(&mut a, b.clone());
+    assert!((a.estimate() - 10000.0).abs() < 500.0, "Bad estimate: {}.", a.estimate());
+    assert_eq!(Some(Ordering::Greater), HyperLogLog::partial_cmp(&a, &b));
+
+    // Duplicates don't change the sketch.
+    let before = a.clone();
+    a.insert(&17);
+    assert_eq!(before, a);
+
+    // Per-key counts.
+    type F = MapUnion<HashMap<&'static str, HyperLogLog<8>>>;
+    let mut visitors: HashMap<_, HyperLogLog<8>> = HashMap::new();
+    for ( page, user ) in [ ( "home", "x" ), ( "home", "y" ), ( "about", "x" ), ( "home", "x" ) ] {
+        let mut hll = HyperLogLog::new();
+        hll.insert(user);
+        F::merge_in(&mut visitors, vec![ ( page, hll ) ].into_iter().collect());
+    }
+    assert_eq!(2, visitors["home"].estimate().round() as u64);
+    assert_eq!(1, visitors["about"].estimate().round() as u64);
+
+    // Items hash the same on every platform and Rust version, so sketches
+    // built by different replicas agree.
+    let mut hll = HyperLogLog::<4>::new();
+    hll.insert(&42_u64);
+    hll.insert("spinach");
+    assert_eq!(&[ 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 2, 0, 0, 0, 0, 0 ], hll.registers());
+}
+
+#[test]
+pub fn test_bloom_filter() {
+    assert_eq!(( 9586, 7 ), bloom_filter_params(1000, 0.01));
+    let mut a = BloomFilter::<9586, 7>::new();
+    let mut b = BloomFilter::<9586, 7>::bottom();
+    for i in 0..500 {
+        a.insert(&i);
+    }
+    for i in 500..1000 {
+        b.insert(&i);
+    }
+    assert!(!a.may_contain(&750));
+    BloomFilter::merge_in(&mut a, b.clone());
+    assert!((0..1000).all(|i| a.may_contain(&i)));
+    assert_eq!(Some(Ordering::Greater), BloomFilter::partial_cmp(&a, &b));
+
+    let false_positives = (1000..11000).filter(|i| a.may_contain(i)).count();
+    assert!(false_positives < 200, "Too many false positives: {}.", false_positives);
+    assert!(0.005 < a.false_positive_rate() && a.false_positive_rate() < 0.02);
+
+    // Mismatched parameters are rejected at runtime by `try_merge_in`.
+    let before = a.clone();
+    assert!(BloomFilter::try_merge_in(&mut a, BloomFilter::<64, 2>::new()).is_err());
+    assert_eq!(before, a);
+    assert!(BloomFilter::try_merge_in(&mut a, b).is_ok());
+    assert_eq!(before, a);
+}
+
+#[test]
+#[should_panic]
+pub fn test_bloom_filter_params_rate() {
+    bloom_filter_params(1000, 1.0);
+}
+
+#[test]
+pub fn test_delta_merge() {
+    let mut max = 3;
+    assert_eq!(None, Max::merge_in_delta(&mut max, 2));
+    assert_eq!(Some(5), Max::merge_in_delta(&mut max, 5));
+
+    let mut set: HashSet<_> = vec![ 1, 2 ].into_iter().collect();
+    let delta = Union::<HashSet<_>>::merge_in_delta(&mut set, vec![ 2, 3 ].into_iter().collect());
+    assert_eq!(Some(vec![ 3 ].into_iter().collect()), delta);
+    assert_eq!(None, Union::<HashSet<_>>::merge_in_delta(&mut set, vec![ 1, 3 ].into_iter().collect()));
+
+    let mut inter: BTreeSet<_> = vec![ 1, 2, 3 ].into_iter().collect();
+    assert_eq!(None, Intersect::<BTreeSet<_>>::merge_in_delta(&mut inter, vec![ 1, 2, 3, 4 ].into_iter().collect()));
+    assert_eq!(Some(vec![ 2 ].into_iter().collect()), Intersect::<BTreeSet<_>>::merge_in_delta(&mut inter, vec![ 2 ].into_iter().collect()));
+
+    type F = MapUnion<HashMap<&'static str, Max<u32>>>;
+    let mut map: HashMap<_, _> = vec![ ( "a", 1 ), ( "b", 5 ) ].into_iter().collect();
+    let delta = F::merge_in_delta(&mut map, vec![ ( "a", 2 ), ( "b", 4 ), ( "c", 1 ) ].into_iter().collect());
+    assert_eq!(Some(vec![ ( "a", 2 ), ( "c", 1 ) ].into_iter().collect()), delta);
+
+    type D = DominatingPair<Max<u32>, Union<HashSet<char>>>;
+    let mut pair = ( 1, vec![ 'x' ].into_iter().collect() );
+    assert_eq!(None, D::merge_in_delta(&mut pair, ( 0, vec![ 'y' ].into_iter().collect() )));
+    let delta = D::merge_in_delta(&mut pair, ( 1, vec![ 'x', 'y' ].into_iter().collect() ));
+    assert_eq!(Some(( 1, vec![ 'y' ].into_iter().collect() )), delta);
+}
+
+#[test]
+pub fn test_merge_in_ref() {
+    let mut max = String::from("b");
+    Max::merge_in_ref(&mut max, &String::from("a"));
+    Max::merge_in_ref(&mut max, &String::from("c"));
+    assert_eq!("c", max);
+
+    let mut min = 3;
+    Min::merge_in_ref(&mut min, &1);
+    assert_eq!(1, min);
+
+    let big: BTreeSet<_> = ( 0..1000 ).collect();
+    let mut set: BTreeSet<_> = vec![ 5, 5000 ].into_iter().collect();
+    Union::<BTreeSet<_>>::merge_in_ref(&mut set, &big);
+    assert_eq!(1001, set.len());
+    Intersect::<BTreeSet<_>>::merge_in_ref(&mut set, &big);
+    assert_eq!(big, set);
+
+    type F = MapUnion<HashMap<&'static str, Union<HashSet<u32>>>>;
+    let mut map: HashMap<_, HashSet<_>> = vec![ ( "a", vec![ 1 ].into_iter().collect() ) ].into_iter().collect();
+    let other: HashMap<_, HashSet<_>> = vec![
+        ( "a", vec![ 2 ].into_iter().collect() ),
+        ( "b", vec![ 3 ].into_iter().collect() ),
+    ].into_iter().collect();
+    F::merge_in_ref(&mut map, &other);
+    assert_eq!(vec![ 1, 2 ].into_iter().collect::<HashSet<_>>(), map["a"]);
+    assert_eq!(vec![ 3 ].into_iter().collect::<HashSet<_>>(), map["b"]);
+    assert_eq!(2, other.len());
+}
+
+#[test]
+pub fn test_merge_all() {
+    let mut max = 3;
+    Max::merge_all(&mut max, vec![ 1, 7, 5 ]);
+    assert_eq!(7, max);
+
+    let mut set: BTreeSet<_> = vec![ 1 ].into_iter().collect();
+    let batch: Vec<BTreeSet<_>> = vec![
+        vec![ 2, 4 ].into_iter().collect(),
+        vec![ 1, 3 ].into_iter().collect(),
+    ];
+    Union::<BTreeSet<_>>::merge_all(&mut set, batch);
+    assert_eq!(vec![ 1, 2, 3, 4 ].into_iter().collect::<BTreeSet<_>>(), set);
+
+    let batch: Vec<BTreeSet<_>> = vec![
+        vec![ 1, 2, 3, 5 ].into_iter().collect(),
+        vec![ 2, 3, 4 ].into_iter().collect(),
+    ];
+    Intersect::<BTreeSet<_>>::merge_all(&mut set, batch);
+    assert_eq!(vec![ 2, 3 ].into_iter().collect::<BTreeSet<_>>(), set);
+    Intersect::<BTreeSet<_>>::merge_all(&mut set, vec![]);
+    assert_eq!(2, set.len());
+
+    type F = MapUnion<BTreeMap<&'static str, Max<u32>>>;
+    let mut map: BTreeMap<_, _> = vec![ ( "a", 1 ) ].into_iter().collect();
+    let batch: Vec<BTreeMap<_, _>> = vec![
+        vec![ ( "a", 3 ), ( "c", 1 ) ].into_iter().collect(),
+        vec![ ( "a", 2 ), ( "b", 2 ) ].into_iter().collect(),
+    ];
+    F::merge_all(&mut map, batch);
+    assert_eq!(vec![ ( "a", 3 ), ( "b", 2 ), ( "c", 1 ) ].into_iter().collect::<BTreeMap<_, _>>(), map);
+
+    // Values of equal keys are merged in run order.
+    let mut map: BTreeMap<_, _> = vec![ ( 1, "a".to_owned() ) ].into_iter().collect();
+    let batch: Vec<BTreeMap<_, _>> = vec![
+        vec![ ( 1, "b".to_owned() ), ( 2, "c".to_owned() ) ].into_iter().collect(),
+        vec![ ( 0, "d".to_owned() ), ( 1, "e".to_owned() ) ].into_iter().collect(),
+    ];
+    map.extend_all(batch, |val, other| val.push_str(&other));
+    assert_eq!(vec![ ( 0, "d" ), ( 1, "abe" ), ( 2, "c" ) ], map.iter().map(|( &k, v )| ( k, v.as_str() )).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn test_bottom_top() {
+    assert_eq!(0, <Max<u32> as Bottom>::bottom());
+    assert_eq!(u32::MAX, <Min<u32> as Bottom>::bottom());
+    assert_eq!(HashSet::<u8>::new(), <Union<HashSet<u8>> as Bottom>::bottom());
+
+    // Bottom is the identity of `merge_in`, and top absorbs.
+    type F = Pair<Max<i64>, MapUnion<HashMap<char, Min<u8>>>>;
+    let val = ( -3, vec![ ( 'a', 4 ) ].into_iter().collect() );
+    let mut merged = F::bottom();
+    F::merge_in(&mut merged, val.clone());
+    assert_eq!(val, merged);
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&F::bottom(), &val));
+
+    type G = ( Min<char>, Intersect<BTreeSet<u8>> );
+    let mut top = G::top();
+    G::merge_in(&mut top, ( 'x', vec![ 1, 2 ].into_iter().collect() ));
+    assert_eq!(G::top(), top);
+
+    assert_eq!(0, PnCounter::<&str>::bottom().value());
+}
+
+#[test]
+pub fn test_dual() {
+    type F = Dual<MapUnion<HashMap<&'static str, Max<u32>>>>;
+
+    let mut val: HashMap<_, _> = vec![ ( "a", 1 ), ( "b", 5 ) ].into_iter().collect();
+    F::merge_in(&mut val, vec![ ( "b", 3 ), ( "c", 2 ) ].into_iter().collect());
+    assert_eq!(vec![ ( "b", 3 ) ].into_iter().collect::<HashMap<_, _>>(), val);
+
+    F::meet_in(&mut val, vec![ ( "c", 2 ) ].into_iter().collect());
+    assert_eq!(vec![ ( "b", 3 ), ( "c", 2 ) ].into_iter().collect::<HashMap<_, _>>(), val);
+
+    // `Min` and `Intersect` are duals.
+    assert_eq!(Some(Ordering::Greater), Min::<u8>::partial_cmp(&1, &2));
+    let small: HashSet<_> = vec![ 1 ].into_iter().collect();
+    let big: HashSet<_> = vec![ 1, 2 ].into_iter().collect();
+    assert_eq!(Some(Ordering::Greater), Intersect::<HashSet<_>>::partial_cmp(&small, &big));
+    assert_eq!(Some(Ordering::Less), Union::<HashSet<_>>::partial_cmp(&small, &big));
+}
+
+#[test]
+pub fn test_lexicographic() {
+    type F = Lexicographic<Union<BTreeSet<char>>, Max<u32>>;
+
+    let a = ( vec![ 'a' ].into_iter().collect(), 5 );
+    let b = ( vec![ 'b' ].into_iter().collect(), 3 );
+    let a2 = ( vec![ 'a' ].into_iter().collect(), 7 );
+    assert_eq!(None, F::partial_cmp(&a, &b));
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&a, &a2));
+
+    // Incomparable first components reset the second to bottom.
+    let mut ab = a.clone();
+    F::merge_in(&mut ab, b.clone());
+    assert_eq!(( vec![ 'a', 'b' ].into_iter().collect(), 0 ), ab);
+    assert_eq!(Some(Ordering::Greater), F::partial_cmp(&ab, &a2));
+
+    // Equal first components no longer hide the second.
+    type D = DominatingPair<Max<u32>, Max<u32>>;
+    assert_eq!(Some(Ordering::Less), D::partial_cmp(&( 1, 2 ), &( 1, 3 )));
+    assert_eq!(Some(Ordering::Greater), D::partial_cmp(&( 2, 0 ), &( 1, 3 )));
+}
+
+#[test]
+pub fn test_bound_adapters() {
+    type F = MapUnion<HashMap<&'static str, WithBot<Max<String>>>>;
+    let mut map = F::bottom();
+    F::merge_in(&mut map, vec![ ( "a", None ), ( "b", Some("x".to_owned()) ) ].into_iter().collect());
+    F::merge_in(&mut map, vec![ ( "a", Some("y".to_owned()) ), ( "b", None ) ].into_iter().collect());
+    assert_eq!(Some("y".to_owned()), map["a"]);
+    assert_eq!(Some("x".to_owned()), map["b"]);
+
+    type P = WithTop<Union<HashSet<u8>>>;
+    let mut poisoned = Some(vec![ 1 ].into_iter().collect());
+    P::merge_in(&mut poisoned, P::top());
+    P::merge_in(&mut poisoned, Some(vec![ 2 ].into_iter().collect()));
+    assert_eq!(None, poisoned);
+    assert_eq!(Some(Ordering::Less), P::partial_cmp(&P::bottom(), &poisoned));
+
+    let mut any = Or::bottom();
+    assert_eq!(None, Or::merge_in_delta(&mut any, false));
+    assert_eq!(Some(true), Or::merge_in_delta(&mut any, true));
+    let mut all = And::bottom();
+    And::merge_in(&mut all, true);
+    assert!(all);
+    And::merge_in(&mut all, false);
+    assert!(!all);
+}
+
+#[test]
+pub fn test_pointwise() {
+    type F = Pointwise<Vec<Max<u32>>>;
+    let mut val = vec![ 1, 5 ];
+    F::merge_in(&mut val, vec![ 3, 2, 7 ]);
+    assert_eq!(vec![ 3, 5, 7 ], val);
+    assert_eq!(Some(Ordering::Less), F::partial_cmp(&vec![ 3, 5 ], &val));
+    assert_eq!(None, F::partial_cmp(&vec![ 3, 6 ], &val));
+
+    type A = Pointwise<[Min<i8>; 3]>;
+    let mut arr = A::bottom();
+    A::merge_in(&mut arr, [ 1, 2, 3 ]);
+    A::merge_in(&mut arr, [ 4, 0, 5 ]);
+    assert_eq!([ 1, 0, 3 ], arr);
+    assert_eq!(Some(Ordering::Greater), A::partial_cmp(&arr, &[ 1, 2, 3 ]));
+}
+
+#[test]
+pub fn test_range_to_zero() {
+    let mut val = -3.5;
+    RangeToZero::merge_in(&mut val, -1.25);
+    assert_eq!(-1.25, val);
+    RangeToZero::merge_in(&mut val, 2.0);
+    assert_eq!(0.0, val);
+    assert_eq!(Some(Ordering::Less), RangeToZero::<i64>::partial_cmp(&-7, &-2));
+    assert_eq!(None, RangeToZero::<i64>::partial_cmp(&-7, &2));
+    assert_eq!(Some(Ordering::Greater), RangeToZero::<i64>::partial_cmp(&0, &2));
+
+    // Equal magnitudes with opposite signs merge to zero, in either order.
+    let mut val = -4_i8;
+    RangeToZero::merge_in(&mut val, 4);
+    assert_eq!(0, val);
+    let mut val = 4_i8;
+    RangeToZero::merge_in(&mut val, -4);
+    assert_eq!(0, val);
+    assert_eq!(None, RangeToZero::<i8>::partial_cmp(&-4, &4));
+    let mut val = 0.5_f32;
+    RangeToZero::merge_in(&mut val, -0.5);
+    assert_eq!(0.0, val);
+    assert_eq!(None, RangeToZero::<f32>::partial_cmp(&0.5, &-0.5));
+}
+
+#[test]
+pub fn test_interval_fixpoint() {
+    // Analyzes `i = 0; while i < 100 { i += 1; }`, for `i` at the loop head.
+    type F = Interval<i32>;
+    let entry = ( 0, 0 );
+    let step = |( lo, hi ): ( i32, i32 )| ( lo + 1, hi.min(99) + 1 );
+    let head = |x: ( i32, i32 )| {
+        let mut next = entry;
+        F::merge_in(&mut next, step(x));
+        next
+    };
+
+    // Widen until stable.
+    let mut x = entry;
+    loop {
+        let mut widened = x;
+        F::widen_in(&mut widened, head(x));
+        if widened == x {
+            break;
+        }
+        x = widened;
+    }
+    assert_eq!(( 0, i32::MAX ), x);
+
+    // Then narrow.
+    loop {
+        let mut narrowed = x;
+        F::narrow_in(&mut narrowed, head(x));
+        if narrowed == x {
+            break;
+        }
+        x = narrowed;
+    }
+    assert_eq!(( 0, 100 ), x);
+}
+
+#[test]
+pub fn test_floats() {
+    let mut val = f64::NAN;
+    FloatMax::<_, NanIsBottom>::merge_in(&mut val, -1.0);
+    assert_eq!(-1.0, val);
+    FloatMax::<_, NanIsBottom>::merge_in(&mut val, f64::NAN);
+    assert_eq!(-1.0, val);
+
+    FloatMax::<_, NanIsTop>::merge_in(&mut val, f64::NAN);
+    assert!(val.is_nan());
+    FloatMax::<_, NanIsTop>::merge_in(&mut val, f64::INFINITY);
+    assert!(val.is_nan());
+
+    let mut val = 0.0_f32;
+    FloatMin::<_, TotalOrder>::merge_in(&mut val, -0.0);
+    assert!(val.is_sign_negative());
+    assert_eq!(Some(Ordering::Less), FloatMax::<f32, TotalOrder>::partial_cmp(&-f32::NAN, &f32::NEG_INFINITY));
+    assert!(FloatMin::<f32, TotalOrder>::bottom().is_nan());
+
+    // Values the policy considers equal still merge to the same bits.
+    let mut a = -0.0_f64;
+    FloatMax::<_, NanIsBottom>::merge_in(&mut a, 0.0);
+    let mut b = 0.0_f64;
+    FloatMax::<_, NanIsBottom>::merge_in(&mut b, -0.0);
+    assert_eq!(a.to_bits(), b.to_bits());
+
+    type F = MapUnion<HashMap<&'static str, FloatMax<f64, NanIsBottom>>>;
+    let mut metrics = F::bottom();
+    F::merge_in(&mut metrics, vec![ ( "latency", 1.5 ), ( "load", f64::NAN ) ].into_iter().collect());
+    F::merge_in(&mut metrics, vec![ ( "latency", 0.5 ), ( "load", 0.25 ) ].into_iter().collect());
+    assert_eq!(1.5, metrics["latency"]);
+    assert_eq!(0.25, metrics["load"]);
+}
@@ -0,0 +1,100 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use futures::executor::block_on;
+use futures::future;
+
+use spinach::merge::{ Max, Union };
+use spinach::ops::{ Op, ExclMoveOp, ExclRefOp };
+use spinach::ops::{ DeltaLatticeOp, LatticeOp, RefLatticeOp, TeeOp };
+
+
+/// Records every value pushed into it.
+struct RecordOp<T>(Rc<RefCell<Vec<T>>>);
+impl<T> RecordOp<T> {
+    fn create() -> ( Self, Rc<RefCell<Vec<T>>> ) {
+        let record = Rc::new(RefCell::new(Vec::new()));
+        ( RecordOp(record.clone()), record )
+    }
+}
+impl<T> Op for RecordOp<T> {
+    type Domain = T;
+}
+impl<T: Clone> ExclRefOp for RecordOp<T> {
+    type Feedback = future::Ready<()>;
+
+    fn push(&mut self, item: &T) -> Self::Feedback {
+        self.0.borrow_mut().push(item.clone());
+        future::ready(())
+    }
+}
+impl<T> ExclMoveOp for RecordOp<T> {
+    type Feedback = future::Ready<()>;
+
+    fn push(&mut self, item: T) -> Self::Feedback {
+        self.0.borrow_mut().push(item);
+        future::ready(())
+    }
+}
+
+
+#[test]
+pub fn test_lattice_op_push_all() {
+    let ( sink, record ) = RecordOp::create();
+    let mut op = LatticeOp::<Max<u64>, _>::new(sink);
+
+    block_on(op.push_all(vec![ 3, 7, 5 ]));
+    block_on(op.push_all(vec![ 1, 2 ]));
+    block_on(op.push_all(vec![]));
+
+    // One downstream push per batch, each with the merged value.
+    assert_eq!(vec![ 7, 7, 7 ], *record.borrow());
+}
+
+#[test]
+pub fn test_delta_lattice_op() {
+    let set = |items: &[u32]| items.iter().copied().collect::<BTreeSet<_>>();
+
+    let ( sink, record ) = RecordOp::create();
+    let mut op = DeltaLatticeOp::<Union<BTreeSet<u32>>, _>::new(sink);
+
+    block_on(op.push(set(&[ 1, 2 ])));
+    block_on(op.push(set(&[ 2, 3 ])));
+    // Only the new items are forwarded, not the whole value.
+    assert_eq!(vec![ set(&[ 1, 2 ]), set(&[ 3 ]) ], *record.borrow());
+
+    // Nothing new, so nothing is forwarded.
+    block_on(op.push(set(&[ 1, 3 ])));
+    block_on(op.push(set(&[])));
+    assert_eq!(vec![ set(&[ 1, 2 ]), set(&[ 3 ]) ], *record.borrow());
+}
+
+#[test]
+pub fn test_lattice_op_push() {
+    let ( sink, record ) = RecordOp::create();
+    let mut op = LatticeOp::<Max<u64>, _>::new(sink);
+
+    // Unambiguous with both `ExclMoveOp` and `ExclRefOp` in scope.
+    block_on(op.push(5));
+    block_on(op.push(3));
+    assert_eq!(vec![ 5, 5 ], *record.borrow());
+}
+
+#[test]
+pub fn test_ref_lattice_op() {
+    let set = |items: &[u32]| items.iter().copied().collect::<BTreeSet<_>>();
+
+    let ( sink0, record0 ) = RecordOp::create();
+    let ( sink1, record1 ) = RecordOp::create();
+    let mut op = TeeOp::new(
+        RefLatticeOp::<Union<BTreeSet<u32>>, _>::new(sink0),
+        RefLatticeOp::<Union<BTreeSet<u32>>, _>::new(sink1));
+
+    // The fan-out pushes the same `&item` into both lattices.
+    let item = set(&[ 1, 2 ]);
+    block_on(op.push(&item));
+    block_on(op.push(&set(&[ 3 ])));
+    assert_eq!(vec![ set(&[ 1, 2 ]), set(&[ 1, 2, 3 ]) ], *record0.borrow());
+    assert_eq!(*record0.borrow(), *record1.borrow());
+}
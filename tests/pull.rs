@@ -0,0 +1,56 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::task::{ Context, Poll };
+
+use futures::task::noop_waker_ref;
+
+use spinach::merge::Max;
+use spinach::pull::{ MovePullOp, PullOp, RefPullOp };
+use spinach::pull::{ CloneOp, LatticeOp, MAX_BATCH };
+
+
+/// Always-ready stream counting up from zero, never ending. The count is
+/// shared so tests can see how many items were pulled.
+struct CountOp(Rc<Cell<u64>>);
+impl CountOp {
+    fn create() -> ( Self, Rc<Cell<u64>> ) {
+        let count = Rc::new(Cell::new(0));
+        ( CountOp(count.clone()), count )
+    }
+}
+impl PullOp for CountOp {
+    type Domain = u64;
+}
+impl MovePullOp for CountOp {
+    fn poll_next(&mut self, _ctx: &mut Context<'_>) -> Poll<Option<u64>> {
+        let n = self.0.get();
+        self.0.set(n + 1);
+        Poll::Ready(Some(n))
+    }
+}
+
+
+#[test]
+pub fn test_lattice_op() {
+    let mut ctx = Context::from_waker(noop_waker_ref());
+
+    let ( count_op, count ) = CountOp::create();
+    let mut op = LatticeOp::<_, Max<u64>>::new(count_op);
+
+    // Each poll pulls and merges exactly `MAX_BATCH` items.
+    assert_eq!(Poll::Ready(Some(&(MAX_BATCH as u64 - 1))), op.poll_next(&mut ctx));
+    assert_eq!(MAX_BATCH as u64, count.get());
+    assert_eq!(Poll::Ready(Some(&(2 * MAX_BATCH as u64 - 1))), op.poll_next(&mut ctx));
+    assert_eq!(2 * MAX_BATCH as u64, count.get());
+}
+
+#[test]
+pub fn test_lattice_op_always_ready() {
+    let mut ctx = Context::from_waker(noop_waker_ref());
+
+    // The inner op is always ready, so the outer op must stop on its own.
+    let ( count_op, _ ) = CountOp::create();
+    let inner = LatticeOp::<_, Max<u64>>::new(count_op);
+    let mut op = LatticeOp::<_, Max<u64>>::new(CloneOp::new(inner));
+    assert_eq!(Poll::Ready(Some(&(MAX_BATCH as u64 * MAX_BATCH as u64 - 1))), op.poll_next(&mut ctx));
+}
@@ -45,7 +45,7 @@ pub async fn test_basic() -> Result<(), String> {
 
     // Set up pipes.
     let ( write_pipe, readers_pipe ) = SplitOp::create();
-    let write_pipe = LatticeOp::<MapUnion<HashMap<&'static str, Max<&'static str>>>, _>::new(HashMap::new(), write_pipe);
+    let write_pipe = LatticeOp::<MapUnion<HashMap<&'static str, Max<&'static str>>>, _>::new(write_pipe);
     let write_pipe = MapFilterOp::new(KvToHashmap, write_pipe);
     let mut write_pipe = write_pipe;
 